@@ -1,8 +1,12 @@
-use crate::{commands::InsertOrder, generator::Generate, sections::OutputSection, *};
+use crate::{generator::Generate, sections::OutputSection, whitespace::opt_space, *};
+use nom::combinator::all_consuming;
+use nom::sequence::delimited;
+use std::str::FromStr;
 
 #[derive(Default)]
 pub struct LinkerScriptBuilder {
     memory_builder: MemoryBuilder,
+    program_headers_builder: ProgramHeadersBuilder,
     section_builder: SectionBuilder,
     commands: Vec<Command>,
     statements: Vec<Statement>,
@@ -15,6 +19,11 @@ impl LinkerScriptBuilder {
         self
     }
 
+    pub fn with_program_headers(mut self, program_headers_builder: ProgramHeadersBuilder) -> Self {
+        self.program_headers_builder = program_headers_builder;
+        self
+    }
+
     pub fn with_sections(mut self, section_builder: SectionBuilder) -> Self {
         self.section_builder = section_builder;
         self
@@ -48,14 +57,32 @@ impl LinkerScriptBuilder {
 
 impl Generate for LinkerScriptBuilder {
     fn generate(self) -> String {
-        let mut root_items = vec![
-            RootItem::Memory {
-                regions: self.memory_builder.regions,
-            },
-            RootItem::Sections {
+        // Empty blocks are left out, so that a builder with only sections and
+        // an INSERT command produces a fragment script
+        let mut root_items = Vec::new();
+        if !self.memory_builder.regions.is_empty() {
+            root_items.push(RootItem::Memory {
+                commands: self
+                    .memory_builder
+                    .regions
+                    .into_iter()
+                    .map(MemoryCommand::Region)
+                    .collect(),
+                span: SourceSpan::default(),
+            });
+        }
+        if !self.program_headers_builder.headers.is_empty() {
+            root_items.push(RootItem::Phdrs {
+                headers: self.program_headers_builder.headers,
+                span: SourceSpan::default(),
+            });
+        }
+        if !self.section_builder.sections.is_empty() {
+            root_items.push(RootItem::Sections {
                 list: self.section_builder.sections,
-            },
-        ];
+                span: SourceSpan::default(),
+            });
+        }
         for command in self.commands {
             root_items.push(RootItem::Command(command));
         }
@@ -77,42 +104,90 @@ pub struct MemoryBuilder {
 }
 
 impl MemoryBuilder {
-    pub fn with_region(mut self, name: &str, origin: u64, length: u64) -> Self {
+    pub fn with_region(
+        mut self,
+        name: &str,
+        attributes: impl Into<RegionAttributes>,
+        origin: impl Into<Expression>,
+        length: impl Into<Expression>,
+    ) -> Self {
         self.regions.push(Region {
             name: name.to_string(),
-            origin,
-            length,
+            attributes: attributes.into(),
+            origin: origin.into(),
+            length: length.into(),
+            span: SourceSpan::default(),
         });
         self
     }
 
-    pub fn with_adjacent_region(mut self, name: &str, length: u64) -> Self {
+    /// Adds a region that starts where the last one ends. The origin is
+    /// folded to a number when the last region is constant, and refers to it
+    /// through `ORIGIN()` and `LENGTH()` otherwise.
+    pub fn with_adjacent_region(
+        mut self,
+        name: &str,
+        attributes: impl Into<RegionAttributes>,
+        length: impl Into<Expression>,
+    ) -> Self {
         let last_region = self.regions.last().unwrap();
+        let origin = match (last_region.origin_value(), last_region.length_value()) {
+            (Some(origin), Some(length)) => Expression::Number(origin + length),
+            _ => Expression::BinaryOp {
+                left: Box::new(Expression::Call {
+                    function: "ORIGIN".into(),
+                    arguments: vec![Expression::Ident(last_region.name.clone())],
+                    span: SourceSpan::default(),
+                }),
+                operator: BinaryOperator::Plus,
+                right: Box::new(Expression::Call {
+                    function: "LENGTH".into(),
+                    arguments: vec![Expression::Ident(last_region.name.clone())],
+                    span: SourceSpan::default(),
+                }),
+                span: SourceSpan::default(),
+            },
+        };
         self.regions.push(Region {
             name: name.to_string(),
-            origin: last_region.origin + last_region.length,
-            length,
+            attributes: attributes.into(),
+            origin,
+            length: length.into(),
+            span: SourceSpan::default(),
         });
         self
     }
 
+    /// Returns the distance between the lowest and highest address covered by
+    /// the regions whose origin and length are constant.
     pub fn span(&self) -> u64 {
-        let min = self
+        let bounds: Vec<(u64, u64)> = self
             .regions
             .iter()
-            .map(|region| region.origin)
-            .min()
-            .unwrap_or(0);
-        let max = self
-            .regions
+            .filter_map(|region| Some((region.origin_value()?, region.length_value()?)))
+            .collect();
+        let min = bounds.iter().map(|(origin, _)| *origin).min().unwrap_or(0);
+        let max = bounds
             .iter()
-            .map(|region| region.origin + region.length)
+            .map(|(origin, length)| origin + length)
             .max()
             .unwrap_or(0);
         max - min
     }
 }
 
+#[derive(Default)]
+pub struct ProgramHeadersBuilder {
+    pub headers: Vec<ProgramHeader>,
+}
+
+impl ProgramHeadersBuilder {
+    pub fn with_header(mut self, header: ProgramHeader) -> Self {
+        self.headers.push(header);
+        self
+    }
+}
+
 #[derive(Default)]
 pub struct SectionBuilder {
     sections: Vec<SectionCommand>,
@@ -130,6 +205,7 @@ impl SectionBuilder {
                 name: name.to_string(),
                 operator,
                 expression: Box::new(expression),
+                span: SourceSpan::default(),
             }));
         self
     }
@@ -144,6 +220,11 @@ impl SectionBuilder {
             .push(SectionCommand::OutputSection(output_section));
         self
     }
+
+    pub fn with_overlay(mut self, overlay: Overlay) -> Self {
+        self.sections.push(SectionCommand::Overlay(overlay));
+        self
+    }
 }
 
 impl Command {
@@ -151,12 +232,14 @@ impl Command {
         Command::Call {
             name: name.to_string(),
             arguments: arguments.into_iter().map(|expr| expr.into()).collect(),
+            span: SourceSpan::default(),
         }
     }
 
     pub fn include(file: impl ToString) -> Self {
         Command::Include {
             file: file.to_string(),
+            span: SourceSpan::default(),
         }
     }
 
@@ -164,6 +247,52 @@ impl Command {
         Command::Insert {
             order,
             section: section.to_string(),
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn nullary(command: NullaryCommand) -> Self {
+        Command::Nullary {
+            command,
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn input(files: impl IntoIterator<Item = InputFile>) -> Self {
+        Command::Input {
+            files: files.into_iter().collect(),
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn group(files: impl IntoIterator<Item = InputFile>) -> Self {
+        Command::Group {
+            files: files.into_iter().collect(),
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn extern_symbols(symbols: impl IntoIterator<Item = impl ToString>) -> Self {
+        Command::Extern {
+            symbols: symbols
+                .into_iter()
+                .map(|symbol| symbol.to_string())
+                .collect(),
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn startup(file: impl ToString) -> Self {
+        Command::Startup {
+            file: file.to_string(),
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn search_dir(path: impl ToString) -> Self {
+        Command::SearchDir {
+            path: path.to_string(),
+            span: SourceSpan::default(),
         }
     }
 }
@@ -234,8 +363,149 @@ impl OutputSection {
         self
     }
 
+    pub fn phdr(mut self, phdr_name: impl ToString) -> Self {
+        self.phdrs.push(phdr_name.to_string());
+        self
+    }
+
+    pub fn fillexp(mut self, expr: Expression) -> Self {
+        self.fillexp = Some(Box::new(expr));
+        self.fill_pattern = None;
+        self
+    }
+
+    /// `=fillexp` with a pattern of any length
+    pub fn fill_pattern(mut self, pattern: impl Into<Vec<u8>>) -> Self {
+        self.fill_pattern = Some(pattern.into());
+        self.fillexp = None;
+        self
+    }
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start(mut self, expr: Expression) -> Self {
+        self.start = Some(Box::new(expr));
+        self
+    }
+
+    pub fn nocrossrefs(mut self, nocrossrefs: bool) -> Self {
+        self.nocrossrefs = nocrossrefs;
+        self
+    }
+
+    pub fn lma_address(mut self, expr: Expression) -> Self {
+        self.lma_address = Some(Box::new(expr));
+        self
+    }
+
+    pub fn add_section(mut self, section: OverlaySection) -> Self {
+        self.sections.push(section);
+        self
+    }
+
+    pub fn region(mut self, region_name: impl ToString) -> Self {
+        self.region = Some(region_name.to_string());
+        self
+    }
+
+    pub fn lma_region(mut self, region_name: impl ToString) -> Self {
+        self.lma_region = Some(region_name.to_string());
+        self
+    }
+
+    pub fn phdr(mut self, phdr_name: impl ToString) -> Self {
+        self.phdrs.push(phdr_name.to_string());
+        self
+    }
+
+    pub fn fillexp(mut self, expr: Expression) -> Self {
+        self.fillexp = Some(Box::new(expr));
+        self.fill_pattern = None;
+        self
+    }
+
+    /// `=fillexp` with a pattern of any length
+    pub fn fill_pattern(mut self, pattern: impl Into<Vec<u8>>) -> Self {
+        self.fill_pattern = Some(pattern.into());
+        self.fillexp = None;
+        self
+    }
+}
+
+impl OverlaySection {
+    pub fn new(name: impl ToString) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_command(mut self, command: OutputSectionCommand) -> Self {
+        self.content.push(command);
+        self
+    }
+
+    pub fn add_commands(
+        mut self,
+        commands: impl IntoIterator<Item = OutputSectionCommand>,
+    ) -> Self {
+        self.content.extend(commands);
+        self
+    }
+
+    pub fn phdr(mut self, phdr_name: impl ToString) -> Self {
+        self.phdrs.push(phdr_name.to_string());
+        self
+    }
+
     pub fn fillexp(mut self, expr: Expression) -> Self {
         self.fillexp = Some(Box::new(expr));
+        self.fill_pattern = None;
+        self
+    }
+
+    /// `=fillexp` with a pattern of any length
+    pub fn fill_pattern(mut self, pattern: impl Into<Vec<u8>>) -> Self {
+        self.fill_pattern = Some(pattern.into());
+        self.fillexp = None;
+        self
+    }
+}
+
+impl ProgramHeader {
+    pub fn new(name: impl ToString, p_type: ProgramHeaderType) -> Self {
+        Self {
+            name: name.to_string(),
+            p_type,
+            filehdr: false,
+            phdrs: false,
+            at: None,
+            flags: None,
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn filehdr(mut self, filehdr: bool) -> Self {
+        self.filehdr = filehdr;
+        self
+    }
+
+    pub fn phdrs(mut self, phdrs: bool) -> Self {
+        self.phdrs = phdrs;
+        self
+    }
+
+    pub fn at(mut self, expr: Expression) -> Self {
+        self.at = Some(Box::new(expr));
+        self
+    }
+
+    pub fn flags(mut self, expr: Expression) -> Self {
+        self.flags = Some(Box::new(expr));
         self
     }
 }
@@ -248,6 +518,15 @@ impl OutputSectionCommand {
     pub fn fill(expression: Expression) -> Self {
         OutputSectionCommand::Fill {
             expr: Box::new(expression),
+            span: SourceSpan::default(),
+        }
+    }
+
+    /// `FILL` with a pattern of any length
+    pub fn fill_pattern(pattern: impl Into<Vec<u8>>) -> Self {
+        OutputSectionCommand::FillPattern {
+            pattern: pattern.into(),
+            span: SourceSpan::default(),
         }
     }
 
@@ -255,6 +534,33 @@ impl OutputSectionCommand {
         OutputSectionCommand::Data {
             d_type,
             value: Box::new(expression),
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn asciz(string: impl ToString) -> Self {
+        OutputSectionCommand::Asciz {
+            string: string.to_string(),
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn linker_version() -> Self {
+        OutputSectionCommand::LinkerVersion {
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn constructors(sorted: bool) -> Self {
+        OutputSectionCommand::Constructors {
+            sorted,
+            span: SourceSpan::default(),
+        }
+    }
+
+    pub fn create_object_symbols() -> Self {
+        OutputSectionCommand::CreateObjectSymbols {
+            span: SourceSpan::default(),
         }
     }
 
@@ -263,8 +569,10 @@ impl OutputSectionCommand {
         sections: impl IntoIterator<Item = SectionPattern>,
     ) -> Self {
         OutputSectionCommand::InputSection {
+            flags: None,
             file,
             sections: sections.into_iter().collect(),
+            span: SourceSpan::default(),
         }
     }
 
@@ -273,10 +581,24 @@ impl OutputSectionCommand {
         sections: impl IntoIterator<Item = SectionPattern>,
     ) -> Self {
         OutputSectionCommand::KeepInputSection {
+            flags: None,
             file,
             sections: sections.into_iter().collect(),
+            span: SourceSpan::default(),
         }
     }
+
+    /// Restricts an input section description to sections with the given
+    /// flags, e.g. `"SHF_WRITE & !SHF_EXECINSTR".parse()?`. Other commands
+    /// are returned unchanged.
+    pub fn input_section_flags(mut self, value: InputSectionFlags) -> Self {
+        if let OutputSectionCommand::InputSection { flags, .. }
+        | OutputSectionCommand::KeepInputSection { flags, .. } = &mut self
+        {
+            *flags = Some(value);
+        }
+        self
+    }
 }
 
 impl SectionPattern {
@@ -284,20 +606,24 @@ impl SectionPattern {
         Self::Simple(s.to_string())
     }
 
-    pub fn sort_by_name(s: impl ToString) -> Self {
-        Self::SortByName(s.to_string())
+    pub fn sort_by_name(pattern: impl Into<SectionPattern>) -> Self {
+        Self::SortByName(Box::new(pattern.into()))
     }
 
-    pub fn sort_by_alignment(s: impl ToString) -> Self {
-        Self::SortByAlignment(s.to_string())
+    pub fn sort_by_alignment(pattern: impl Into<SectionPattern>) -> Self {
+        Self::SortByAlignment(Box::new(pattern.into()))
     }
 
-    pub fn sort_by_init_priority(s: impl ToString) -> Self {
-        Self::SortByInitPriority(s.to_string())
+    pub fn sort_by_init_priority(pattern: impl Into<SectionPattern>) -> Self {
+        Self::SortByInitPriority(Box::new(pattern.into()))
     }
 
-    pub fn sort_none(s: impl ToString) -> Self {
-        Self::SortNone(s.to_string())
+    pub fn sort_none(pattern: impl Into<SectionPattern>) -> Self {
+        Self::SortNone(Box::new(pattern.into()))
+    }
+
+    pub fn reverse(pattern: impl Into<SectionPattern>) -> Self {
+        Self::Reverse(Box::new(pattern.into()))
     }
 
     pub fn exclude_file(
@@ -311,6 +637,18 @@ impl SectionPattern {
     }
 }
 
+impl From<&str> for SectionPattern {
+    fn from(pattern: &str) -> Self {
+        Self::Simple(pattern.into())
+    }
+}
+
+impl From<String> for SectionPattern {
+    fn from(pattern: String) -> Self {
+        Self::Simple(pattern)
+    }
+}
+
 impl Statement {
     pub fn assign(
         name: impl ToString,
@@ -321,6 +659,7 @@ impl Statement {
             name: name.to_string(),
             operator,
             expression: Box::new(expression.into()),
+            span: SourceSpan::default(),
         }
     }
 
@@ -328,6 +667,7 @@ impl Statement {
         Self::Hidden {
             name: name.to_string(),
             expression: Box::new(expression.into()),
+            span: SourceSpan::default(),
         }
     }
 
@@ -335,6 +675,7 @@ impl Statement {
         Self::Provide {
             name: name.to_string(),
             expression: Box::new(expression.into()),
+            span: SourceSpan::default(),
         }
     }
 
@@ -342,6 +683,7 @@ impl Statement {
         Self::ProvideHidden {
             name: name.to_string(),
             expression: Box::new(expression.into()),
+            span: SourceSpan::default(),
         }
     }
 
@@ -349,6 +691,7 @@ impl Statement {
         Self::Assert {
             expr: Box::new(expression.into()),
             text: text.to_string(),
+            span: SourceSpan::default(),
         }
     }
 }
@@ -361,6 +704,49 @@ impl From<&str> for Expression {
     }
 }
 
+impl From<u64> for Expression {
+    fn from(value: u64) -> Self {
+        Expression::Number(value)
+    }
+}
+
+impl FromStr for RegionAttributes {
+    type Err = ParseError;
+
+    /// Parses attributes as written between the parentheses of a region,
+    /// e.g. `"rwx"` or `"!w"`.
+    fn from_str(value: &str) -> Result<Self, ParseError> {
+        all_consuming(delimited(opt_space, memory::attribute_list, opt_space))(value)
+            .map(|(_, attributes)| attributes)
+            .map_err(|err| ParseError::new(value, err))
+    }
+}
+
+impl FromStr for InputSectionFlags {
+    type Err = ParseError;
+
+    /// Parses flags as written between the parentheses of
+    /// `INPUT_SECTION_FLAGS`, e.g. `"SHF_WRITE & !SHF_EXECINSTR"`.
+    fn from_str(value: &str) -> Result<Self, ParseError> {
+        all_consuming(delimited(
+            opt_space,
+            sections::input_section_flag_list,
+            opt_space,
+        ))(value)
+        .map(|(_, flags)| flags)
+        .map_err(|err| ParseError::new(value, err))
+    }
+}
+
+impl From<RegionFlags> for RegionAttributes {
+    fn from(flags: RegionFlags) -> Self {
+        RegionAttributes {
+            flags,
+            inverted: RegionFlags::empty(),
+        }
+    }
+}
+
 pub fn kb(value: u64) -> u64 {
     value * 1024
 }
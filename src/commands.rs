@@ -1,14 +1,20 @@
+use super::error::{tag, IResult};
 use super::expressions::expression;
 use super::expressions::Expression;
-use super::idents::{pattern, symbol};
+use super::idents::{file_name, pattern, string, symbol};
+use super::span::{spanned, SourceSpan};
 use super::whitespace::{opt_space, space};
 use nom::branch::alt;
-use nom::bytes::complete::tag;
+use nom::character::complete::satisfy;
+use nom::combinator::cut;
 use nom::combinator::map;
+use nom::combinator::not;
 use nom::combinator::opt;
+use nom::multi::many0;
 use nom::multi::separated_list1;
 use nom::sequence::pair;
-use nom::IResult;
+use nom::sequence::preceded;
+use nom::sequence::terminated;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum InsertOrder {
@@ -16,19 +22,64 @@ pub enum InsertOrder {
     After,
 }
 
+/// Command that takes no arguments.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NullaryCommand {
+    ForceCommonAllocation,
+    InhibitCommonAllocation,
+    ForceGroupAllocation,
+}
+
+/// Entry of the file list of `INPUT` or `GROUP`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum InputFile {
+    File(String),
+    /// `-lname`, looked up as `libname.so` or `libname.a` in the library
+    /// search paths
+    Library(String),
+    /// `AS_NEEDED(...)`, files only linked if they resolve a reference
+    AsNeeded(Vec<InputFile>),
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Command {
-    //Simple { name: String },
+    Nullary {
+        command: NullaryCommand,
+        span: SourceSpan,
+    },
     Call {
         name: String,
         arguments: Vec<Expression>,
+        span: SourceSpan,
     },
     Include {
         file: String,
+        span: SourceSpan,
     },
     Insert {
         order: InsertOrder,
         section: String,
+        span: SourceSpan,
+    },
+    Input {
+        files: Vec<InputFile>,
+        span: SourceSpan,
+    },
+    Group {
+        files: Vec<InputFile>,
+        span: SourceSpan,
+    },
+    Extern {
+        symbols: Vec<String>,
+        span: SourceSpan,
+    },
+    Startup {
+        file: String,
+        span: SourceSpan,
+    },
+    SearchDir {
+        path: String,
+        span: SourceSpan,
     },
 }
 
@@ -39,6 +90,106 @@ fn inset_order(input: &str) -> IResult<&str, InsertOrder> {
     ))(input)
 }
 
+fn nullary(input: &str) -> IResult<&str, Command> {
+    let (input, command) = alt((
+        map(tag("FORCE_COMMON_ALLOCATION"), |_| {
+            NullaryCommand::ForceCommonAllocation
+        }),
+        map(tag("INHIBIT_COMMON_ALLOCATION"), |_| {
+            NullaryCommand::InhibitCommonAllocation
+        }),
+        map(tag("FORCE_GROUP_ALLOCATION"), |_| {
+            NullaryCommand::ForceGroupAllocation
+        }),
+    ))(input)?;
+    // The keyword must not be the start of a longer name
+    let (input, _) = not(satisfy(|c| c.is_alphanumeric() || c == '_'))(input)?;
+    let (input, _) = pair(opt_space, opt(tag(";")))(input)?;
+    Ok((
+        input,
+        Command::Nullary {
+            command,
+            span: SourceSpan::default(),
+        },
+    ))
+}
+
+/// Parses a parenthesized list of `item`s, separated by spaces or commas
+fn list<'a, O>(
+    item: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<O>> {
+    preceded(
+        wsc!(tag("(")),
+        cut(terminated(
+            many0(terminated(wsc!(item), opt(wsc!(tag(","))))),
+            tag(")"),
+        )),
+    )
+}
+
+fn input_file(input: &str) -> IResult<&str, InputFile> {
+    alt((
+        map(
+            preceded(tag("AS_NEEDED"), list(input_file)),
+            InputFile::AsNeeded,
+        ),
+        // A quoted name is always a file, even if it starts with `-l`
+        map(string, |name| InputFile::File(name.into())),
+        map(file_name, |name| match name.strip_prefix("-l") {
+            Some(library) => InputFile::Library(library.into()),
+            None => InputFile::File(name.into()),
+        }),
+    ))(input)
+}
+
+fn file_list(input: &str) -> IResult<&str, Command> {
+    let (input, keyword) = alt((tag("INPUT"), tag("GROUP")))(input)?;
+    let (input, files) = list(input_file)(input)?;
+    let (input, _) = pair(opt_space, opt(tag(";")))(input)?;
+    let span = SourceSpan::default();
+    Ok((
+        input,
+        match keyword {
+            "INPUT" => Command::Input { files, span },
+            _ => Command::Group { files, span },
+        },
+    ))
+}
+
+fn extern_symbols(input: &str) -> IResult<&str, Command> {
+    let (input, _) = tag("EXTERN")(input)?;
+    let (input, symbols) = list(symbol)(input)?;
+    let (input, _) = pair(opt_space, opt(tag(";")))(input)?;
+    Ok((
+        input,
+        Command::Extern {
+            symbols: symbols.into_iter().map(String::from).collect(),
+            span: SourceSpan::default(),
+        },
+    ))
+}
+
+fn file_command(input: &str) -> IResult<&str, Command> {
+    let (input, keyword) = alt((tag("STARTUP"), tag("SEARCH_DIR")))(input)?;
+    let (input, file) =
+        preceded(wsc!(tag("(")), cut(terminated(file_name, wsc!(tag(")")))))(input)?;
+    let (input, _) = opt(tag(";"))(input)?;
+    let span = SourceSpan::default();
+    Ok((
+        input,
+        match keyword {
+            "STARTUP" => Command::Startup {
+                file: file.into(),
+                span,
+            },
+            _ => Command::SearchDir {
+                path: file.into(),
+                span,
+            },
+        },
+    ))
+}
+
 fn call(input: &str) -> IResult<&str, Command> {
     let (input, name) = symbol(input)?;
     let (input, _) = wsc!(tag("("))(input)?;
@@ -49,15 +200,28 @@ fn call(input: &str) -> IResult<&str, Command> {
         Command::Call {
             name: name.into(),
             arguments: args,
+            span: SourceSpan::default(),
         },
     ))
 }
 
-fn include(input: &str) -> IResult<&str, Command> {
+/// Parses `INCLUDE file`, which is accepted at every nesting level
+pub fn include_file(input: &str) -> IResult<&str, &str> {
     let (input, _) = pair(tag("INCLUDE"), space)(input)?;
     let (input, file) = pattern(input)?;
     let (input, _) = pair(opt_space, opt(tag(";")))(input)?;
-    Ok((input, Command::Include { file: file.into() }))
+    Ok((input, file))
+}
+
+fn include(input: &str) -> IResult<&str, Command> {
+    let (input, file) = include_file(input)?;
+    Ok((
+        input,
+        Command::Include {
+            file: file.into(),
+            span: SourceSpan::default(),
+        },
+    ))
 }
 
 fn insert(input: &str) -> IResult<&str, Command> {
@@ -70,12 +234,21 @@ fn insert(input: &str) -> IResult<&str, Command> {
         Command::Insert {
             order,
             section: section.into(),
+            span: SourceSpan::default(),
         },
     ))
 }
 
 pub fn command(input: &str) -> IResult<&str, Command> {
-    alt((include, call, insert))(input)
+    spanned(alt((
+        include,
+        nullary,
+        file_list,
+        extern_symbols,
+        file_command,
+        call,
+        insert,
+    )))(input)
 }
 
 #[cfg(test)]
@@ -99,4 +272,86 @@ mod tests {
         assert_done!(command("INSERT BEFORE .text  ;"));
         assert_done!(command("INSERT  AFTER  .text"));
     }
+
+    #[test]
+    fn test_nullary() {
+        assert_done!(
+            command("FORCE_COMMON_ALLOCATION"),
+            Command::Nullary {
+                command: NullaryCommand::ForceCommonAllocation,
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(command("INHIBIT_COMMON_ALLOCATION;"));
+        assert_done!(command("FORCE_GROUP_ALLOCATION ;"));
+        // Only valid inside an output section
+        assert_fail!(command("CONSTRUCTORS"));
+        assert_fail!(command("CREATE_OBJECT_SYMBOLS"));
+        assert!(matches!(
+            command("CONSTRUCTORS_LIST(a)"),
+            Ok((_, Command::Call { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_file_lists() {
+        assert_done!(
+            command("INPUT(a.o -lfoo AS_NEEDED(libbar.so))"),
+            Command::Input {
+                files: vec![
+                    InputFile::File("a.o".into()),
+                    InputFile::Library("foo".into()),
+                    InputFile::AsNeeded(vec![InputFile::File("libbar.so".into())]),
+                ],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            command(
+                "GROUP ( /lib/libc.so.6 /usr/lib/libc_nonshared.a  \
+                 AS_NEEDED ( /lib/ld-linux-x86-64.so.2 ) )"
+            ),
+            Command::Group {
+                files: vec![
+                    InputFile::File("/lib/libc.so.6".into()),
+                    InputFile::File("/usr/lib/libc_nonshared.a".into()),
+                    InputFile::AsNeeded(vec![InputFile::File("/lib/ld-linux-x86-64.so.2".into())]),
+                ],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(command("GROUP(a.o, \"b c.o\", -lm);"));
+        assert_done!(
+            command("INPUT(\"-lx\" -ly)"),
+            Command::Input {
+                files: vec![
+                    InputFile::File("-lx".into()),
+                    InputFile::Library("y".into()),
+                ],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            command("EXTERN(sym1 sym2, sym3)"),
+            Command::Extern {
+                symbols: vec!["sym1".into(), "sym2".into(), "sym3".into()],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            command("STARTUP(crt0.o)"),
+            Command::Startup {
+                file: "crt0.o".into(),
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            command("SEARCH_DIR(\"=/usr/local/lib\");"),
+            Command::SearchDir {
+                path: "=/usr/local/lib".into(),
+                span: SourceSpan::default(),
+            }
+        );
+        assert_fail!(command("STARTUP(a.o b.o)"));
+    }
 }
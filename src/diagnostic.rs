@@ -0,0 +1,98 @@
+use crate::span::Span;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Problem found in a parsed script.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier of the check, e.g. `unknown-region`
+    pub code: &'static str,
+    pub message: String,
+    /// Location of the offending node, if it was parsed
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String, span: Option<Span>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message,
+            span,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: String, span: Option<Span>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            code,
+            message,
+            span,
+        }
+    }
+
+    /// Renders the first line of the offending node in `source`, underlined.
+    /// Empty if the diagnostic has no span.
+    pub fn snippet(&self, source: &str) -> String {
+        let Some(span) = self.span else {
+            return String::new();
+        };
+        let line_end = source[span.start..]
+            .find('\n')
+            .map_or(source.len(), |i| span.start + i);
+        let width = source[span.start..span.end.min(line_end)].chars().count();
+        crate::error::snippet(source, span.start, span.line, span.column, width)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some(span) = self.span {
+            write!(f, " at line {}, column {}", span.line, span.column)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::*;
+
+    #[test]
+    fn test_display() {
+        let source = "SECTIONS {\n  .text : { *(.text) } > ROM\n}\n";
+        let span = Span {
+            start: 13,
+            end: 39,
+            line: 2,
+            column: 3,
+        };
+        let diagnostic =
+            Diagnostic::error("unknown-region", "unknown region `ROM`".into(), Some(span));
+        assert_eq!(
+            diagnostic.to_string(),
+            "error[unknown-region]: unknown region `ROM` at line 2, column 3"
+        );
+        assert_eq!(
+            diagnostic.snippet(source),
+            " --> 2:3\n  |\n2 |   .text : { *(.text) } > ROM\n  |   ^^^^^^^^^^^^^^^^^^^^^^^^^^\n"
+        );
+        assert!(Severity::Error > Severity::Warning);
+    }
+}
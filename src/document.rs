@@ -0,0 +1,360 @@
+use crate::error::ParseError;
+use crate::generator::Generate;
+use crate::memory::MemoryCommand;
+use crate::phdrs::ProgramHeader;
+use crate::script::RootItem;
+use crate::sections::{OutputSectionCommand, OverlaySection, SectionCommand};
+use crate::span::{Span, Spanned};
+
+/// A parsed script that remembers its source, so that it can be rendered
+/// back without losing comments and formatting.
+///
+/// Edit `items` freely. When rendering, every node that is unchanged since
+/// parsing is copied from the source byte for byte, together with the
+/// comments and whitespace around it, and only edited or new nodes are
+/// generated. Memory regions, program headers, output sections and overlays
+/// whose own clauses are unchanged keep the source of their unchanged
+/// content as well.
+///
+/// ```
+/// extern crate ldscript_parser as lds;
+///
+/// let source = "MEMORY {\n  /* boot */\n  ROM : ORIGIN = 0, LENGTH = 1K\n}\n";
+/// let mut document = lds::Document::parse(source).unwrap();
+/// if let lds::RootItem::Memory { commands, .. } = &mut document.items[0] {
+///     if let lds::MemoryCommand::Region(rom) = &mut commands[0] {
+///         rom.length = lds::Expression::from(2048);
+///     }
+/// }
+/// assert_eq!(
+///     document.render(),
+///     "MEMORY {\n  /* boot */\n  ROM : ORIGIN = 0x0, LENGTH = 2K\n}\n"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Document {
+    source: String,
+    original: Vec<RootItem>,
+    pub items: Vec<RootItem>,
+}
+
+impl Document {
+    pub fn parse(source: &str) -> Result<Document, ParseError> {
+        let items = crate::parse(source)?;
+        Ok(Document {
+            source: source.to_string(),
+            original: items.clone(),
+            items,
+        })
+    }
+
+    /// Source the document was parsed from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Renders the script, keeping the source of unchanged nodes.
+    pub fn render(&self) -> String {
+        if self.original.is_empty() {
+            return format!("{}{}", self.source, self.items.clone().generate());
+        }
+        let renderer = Renderer {
+            source: &self.source,
+        };
+        let whole = Span {
+            start: 0,
+            end: self.source.len(),
+            line: 1,
+            column: 1,
+        };
+        renderer
+            .render_container(whole, &self.items, &self.original)
+            .unwrap_or_else(|| self.items.clone().generate())
+    }
+}
+
+impl Generate for Document {
+    fn generate(self) -> String {
+        self.render()
+    }
+}
+
+struct Renderer<'a> {
+    source: &'a str,
+}
+
+impl<'a> Renderer<'a> {
+    fn text(&self, span: Span) -> &'a str {
+        &self.source[span.start..span.end]
+    }
+
+    /// Renders a node, looking up its original by span. Generated text is
+    /// indented by `indent` after its first line.
+    fn render_node<T: Render>(&self, node: &T, originals: &[T], indent: &str) -> String {
+        let original = node.span().and_then(|span| {
+            originals
+                .iter()
+                .find(|original| original.span() == Some(span))
+        });
+        let rendered = match original {
+            Some(original) if original == node => {
+                return self.text(original.span().unwrap()).into()
+            }
+            Some(original) => match node.render_changed(original, self) {
+                Some(rendered) => return rendered,
+                None => node.generate_node(),
+            },
+            None => node.generate_node(),
+        };
+        rendered.replace('\n', &format!("\n{}", indent))
+    }
+
+    /// Renders a container whose own syntax is unchanged: the source up to
+    /// its first original child and after its last one is kept, and the
+    /// children are rendered in between. Gaps between children that were
+    /// adjacent in the source are kept too, new gaps get a line break and the
+    /// indentation of the first original child.
+    fn render_container<T: Render>(
+        &self,
+        span: Span,
+        nodes: &[T],
+        originals: &[T],
+    ) -> Option<String> {
+        let first = originals.first()?.span()?;
+        let last = originals.last()?.span()?;
+        let line_start = self.source[..first.start].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &self.source[line_start..first.start];
+        let indent = if indent.trim().is_empty() { indent } else { "" };
+
+        let mut output = self.source[span.start..first.start].to_string();
+        let mut previous: Option<usize> = None;
+        for (i, node) in nodes.iter().enumerate() {
+            let index = node.span().and_then(|span| {
+                originals
+                    .iter()
+                    .position(|original| original.span() == Some(span))
+            });
+            if i > 0 {
+                match (previous, index) {
+                    (Some(previous), Some(index)) if previous + 1 == index => {
+                        let gap_start = Spanned::span(&originals[previous])?.end;
+                        let gap_end = Spanned::span(&originals[index])?.start;
+                        output.push_str(&self.source[gap_start..gap_end]);
+                    }
+                    _ => {
+                        output.push('\n');
+                        output.push_str(indent);
+                    }
+                }
+            }
+            output.push_str(&self.render_node(node, originals, indent));
+            previous = index;
+        }
+        output.push_str(&self.source[last.end..span.end]);
+        Some(output)
+    }
+}
+
+/// Nodes that can be rendered from a mix of their source and generated text.
+trait Render: Spanned + PartialEq {
+    fn generate_node(&self) -> String;
+
+    /// Renders a node that differs from its original, reusing the source of
+    /// its unchanged parts. Returns `None` to generate the whole node.
+    fn render_changed(&self, _original: &Self, _renderer: &Renderer) -> Option<String> {
+        None
+    }
+}
+
+impl Render for RootItem {
+    fn generate_node(&self) -> String {
+        vec![self.clone()].generate().trim_end().to_string()
+    }
+
+    fn render_changed(&self, original: &Self, renderer: &Renderer) -> Option<String> {
+        let span = original.span()?;
+        match (self, original) {
+            (
+                RootItem::Memory { commands, .. },
+                RootItem::Memory {
+                    commands: originals,
+                    ..
+                },
+            ) => renderer.render_container(span, commands, originals),
+            (
+                RootItem::Sections { list, .. },
+                RootItem::Sections {
+                    list: originals, ..
+                },
+            ) => renderer.render_container(span, list, originals),
+            (
+                RootItem::Phdrs { headers, .. },
+                RootItem::Phdrs {
+                    headers: originals, ..
+                },
+            ) => renderer.render_container(span, headers, originals),
+            _ => None,
+        }
+    }
+}
+
+impl Render for MemoryCommand {
+    fn generate_node(&self) -> String {
+        self.clone().generate()
+    }
+}
+
+impl Render for ProgramHeader {
+    fn generate_node(&self) -> String {
+        self.clone().generate()
+    }
+}
+
+impl Render for SectionCommand {
+    fn generate_node(&self) -> String {
+        self.clone().generate()
+    }
+
+    fn render_changed(&self, original: &Self, renderer: &Renderer) -> Option<String> {
+        let span = original.span()?;
+        match (self, original) {
+            (SectionCommand::OutputSection(section), SectionCommand::OutputSection(original)) => {
+                let mut shell = section.clone();
+                let content = std::mem::take(&mut shell.content);
+                let mut original_shell = original.clone();
+                let originals = std::mem::take(&mut original_shell.content);
+                if shell != original_shell {
+                    return None;
+                }
+                renderer.render_container(span, &content, &originals)
+            }
+            (SectionCommand::Overlay(overlay), SectionCommand::Overlay(original)) => {
+                let mut shell = overlay.clone();
+                let sections = std::mem::take(&mut shell.sections);
+                let mut original_shell = original.clone();
+                let originals = std::mem::take(&mut original_shell.sections);
+                if shell != original_shell {
+                    return None;
+                }
+                renderer.render_container(span, &sections, &originals)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Render for OverlaySection {
+    fn generate_node(&self) -> String {
+        self.clone().generate()
+    }
+
+    fn render_changed(&self, original: &Self, renderer: &Renderer) -> Option<String> {
+        let mut shell = self.clone();
+        let content = std::mem::take(&mut shell.content);
+        let mut original_shell = original.clone();
+        let originals = std::mem::take(&mut original_shell.content);
+        if shell != original_shell {
+            return None;
+        }
+        renderer.render_container(original.span()?, &content, &originals)
+    }
+}
+
+impl Render for OutputSectionCommand {
+    fn generate_node(&self) -> String {
+        self.clone().generate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::*;
+    use crate::{AssignOperator, Expression, OutputSection, Statement};
+    use std::fs::{read_dir, read_to_string};
+
+    #[test]
+    fn test_unchanged() {
+        for entry in read_dir("tests").unwrap() {
+            let path = entry.unwrap().path();
+            println!("testing: {path:?}");
+            let source = read_to_string(path).unwrap();
+            let document = Document::parse(&source).unwrap();
+            assert_eq!(document.render(), source);
+        }
+    }
+
+    #[test]
+    fn test_edits() {
+        let source = "\
+/* Vendor header */
+MEMORY
+{
+  FLASH (rx)  : ORIGIN = 0x08000000, LENGTH = 256K   /* main flash */
+  RAM   (rwx) : ORIGIN = 0x20000000, LENGTH = 64K
+}
+
+SECTIONS
+{
+  .text :
+  {
+    *(.text*)      /* code */
+    *(.rodata*)
+  } > FLASH
+
+  /* data */
+  .data : { *(.data*) } > RAM AT> FLASH
+}
+";
+        let mut document = Document::parse(source).unwrap();
+        let RootItem::Memory { commands, .. } = &mut document.items[0] else {
+            panic!("expected MEMORY");
+        };
+        let MemoryCommand::Region(ram) = &mut commands[1] else {
+            panic!("expected RAM");
+        };
+        ram.length = Expression::from(128 * 1024);
+        let RootItem::Sections { list, .. } = &mut document.items[1] else {
+            panic!("expected SECTIONS");
+        };
+        let SectionCommand::OutputSection(text) = &mut list[0] else {
+            panic!("expected .text");
+        };
+        text.content
+            .push(OutputSectionCommand::statement(Statement::assign(
+                "_etext",
+                AssignOperator::Equals,
+                ".",
+            )));
+        list.push(SectionCommand::OutputSection(
+            OutputSection::new(".bss").region("RAM"),
+        ));
+
+        assert_eq!(
+            document.render(),
+            "\
+/* Vendor header */
+MEMORY
+{
+  FLASH (rx)  : ORIGIN = 0x08000000, LENGTH = 256K   /* main flash */
+  RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 128K
+}
+
+SECTIONS
+{
+  .text :
+  {
+    *(.text*)      /* code */
+    *(.rodata*)
+    _etext = .;
+  } > FLASH
+
+  /* data */
+  .data : { *(.data*) } > RAM AT> FLASH
+  .bss : {
+  } >RAM
+}
+"
+        );
+        assert_eq!(crate::parse(&document.render()).unwrap(), document.items);
+    }
+}
@@ -0,0 +1,289 @@
+use nom::error::{ErrorKind, FromExternalError, ParseError as NomParseError};
+use nom::{Err, InputLength};
+use std::fmt;
+
+pub type IResult<I, O> = nom::IResult<I, O, Error<I>>;
+
+/// Error produced while parsing a linker script or a version script.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    /// Byte offset of the error in the source
+    pub offset: usize,
+    /// Line of the error, starting at 1
+    pub line: usize,
+    /// Column of the error in characters, starting at 1
+    pub column: usize,
+    /// Enclosing constructs, innermost first, e.g. "output section `.data`"
+    pub context: Vec<String>,
+    /// Tokens or constructs that would have been accepted at this position
+    pub expected: Vec<String>,
+}
+
+impl ParseError {
+    pub(crate) fn new(source: &str, err: Err<Error<&str>>) -> Self {
+        let err = match err {
+            Err::Error(e) | Err::Failure(e) => e,
+            Err::Incomplete(_) => Error::from_error_kind("", ErrorKind::Complete),
+        };
+        let offset = source.len() - err.input.len();
+        let (line, column) = line_column(source, offset);
+        let mut expected: Vec<String> = err.expected.iter().map(|e| e.to_string()).collect();
+        expected.sort();
+        expected.dedup();
+        ParseError {
+            offset,
+            line,
+            column,
+            context: err.context,
+            expected,
+        }
+    }
+
+    /// Renders the line of `source` containing the error, with a caret line
+    /// underlining the offending token.
+    pub fn snippet(&self, source: &str) -> String {
+        let line_end = source[self.offset..]
+            .find('\n')
+            .map_or(source.len(), |i| self.offset + i);
+        let token_len = source[self.offset..line_end]
+            .chars()
+            .take_while(|c| !c.is_whitespace())
+            .count();
+        snippet(source, self.offset, self.line, self.column, token_len)
+    }
+}
+
+/// Renders the line of `source` containing `offset`, with `width` carets
+/// under it starting at `column`.
+pub(crate) fn snippet(
+    source: &str,
+    offset: usize,
+    line: usize,
+    column: usize,
+    width: usize,
+) -> String {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i);
+    let text = source[line_start..line_end].trim_end_matches('\r');
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+    format!(
+        "{gutter}--> {}:{}\n{gutter} |\n{number} | {text}\n{gutter} | {}{}\n",
+        line,
+        column,
+        " ".repeat(column - 1),
+        "^".repeat(width.max(1)),
+    )
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match self.expected.as_slice() {
+            [] => write!(f, "unexpected input")?,
+            [expected] => write!(f, "expected {}", expected)?,
+            expected => write!(f, "expected one of {}", expected.join(", "))?,
+        }
+        for context in &self.context {
+            write!(f, ", in {}", context)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expected {
+    Token(&'static str),
+    Construct(&'static str),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expected::Token(token) => write!(f, "`{}`", token),
+            Expected::Construct(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Error type threaded through the nom parsers. Keeps the furthest failure
+/// position and merges what was expected there by every alternative.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Error<I> {
+    pub input: I,
+    pub kind: ErrorKind,
+    pub expected: Vec<Expected>,
+    pub context: Vec<String>,
+}
+
+impl<I> Error<I> {
+    fn expecting(input: I, expected: Expected) -> Self {
+        Error {
+            input,
+            kind: ErrorKind::Tag,
+            expected: vec![expected],
+            context: vec![],
+        }
+    }
+}
+
+impl<I: InputLength> NomParseError<I> for Error<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        Error {
+            input,
+            kind,
+            expected: vec![],
+            context: vec![],
+        }
+    }
+
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(mut self, mut other: Self) -> Self {
+        match self.input.input_len().cmp(&other.input.input_len()) {
+            std::cmp::Ordering::Less => self,
+            std::cmp::Ordering::Greater => other,
+            std::cmp::Ordering::Equal => {
+                other.expected.append(&mut self.expected);
+                if other.context.len() < self.context.len() {
+                    other.context = self.context;
+                }
+                other
+            }
+        }
+    }
+}
+
+impl<I: InputLength, E> FromExternalError<I, E> for Error<I> {
+    fn from_external_error(input: I, kind: ErrorKind, _e: E) -> Self {
+        Error::from_error_kind(input, kind)
+    }
+}
+
+/// Recognizes a fixed token, recording it as expected on failure.
+pub fn tag<'a>(token: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    move |input: &'a str| match input.strip_prefix(token) {
+        Some(rest) => Ok((rest, &input[..token.len()])),
+        None => Err(Err::Error(Error::expecting(input, Expected::Token(token)))),
+    }
+}
+
+/// Reports a failure of `parser` at its starting position as `name` being
+/// expected, instead of the tokens tried by its internals.
+pub fn expected<'a, O, F>(
+    name: &'static str,
+    mut parser: F,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O>
+where
+    F: FnMut(&'a str) -> IResult<&'a str, O>,
+{
+    move |input: &'a str| match parser(input) {
+        Err(Err::Error(e)) if e.input.len() == input.len() => Err(Err::Error(Error::expecting(
+            input,
+            Expected::Construct(name),
+        ))),
+        result => result,
+    }
+}
+
+/// Adds the construct being parsed to the context of any error from `parser`.
+pub fn context<'a, O, F, C>(context: C, mut parser: F) -> impl FnMut(&'a str) -> IResult<&'a str, O>
+where
+    F: FnMut(&'a str) -> IResult<&'a str, O>,
+    C: Fn() -> String,
+{
+    move |input: &'a str| {
+        parser(input).map_err(|err| {
+            err.map(|mut e| {
+                e.context.push(context());
+                e
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::*;
+
+    #[test]
+    fn test_line_column() {
+        assert_eq!(line_column("abc", 0), (1, 1));
+        assert_eq!(line_column("abc\ndef", 5), (2, 2));
+        assert_eq!(line_column("a\n\n", 3), (3, 1));
+    }
+
+    fn parse_error(source: &str) -> ParseError {
+        ParseError::new(source, crate::script::parse(source).unwrap_err())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = parse_error("SECTIONS {\n  .data : {\n    *(.data)\n    LONG(\n  }\n}");
+        assert_eq!((error.line, error.column), (4, 9));
+        assert_eq!(error.expected, vec!["`}`"]);
+        assert_eq!(error.context, vec!["output section `.data`", "SECTIONS"]);
+
+        let error = parse_error("MEMORY {\n  ROM (rx) : ORIGN = 0, LENGTH = 4\n}");
+        assert_eq!((error.line, error.column), (2, 14));
+        assert_eq!(error.expected, vec!["`ORIGIN`", "`o`", "`org`"]);
+        assert_eq!(error.context, vec!["memory region `ROM`", "MEMORY"]);
+
+        let error = parse_error("MEMORY {\n  ROM : ORIGIN = 0, LENGTH = \n}");
+        assert_eq!((error.line, error.column), (3, 1));
+        assert_eq!(error.expected, vec!["expression"]);
+
+        let error = parse_error("ENTRY(_start)\n}");
+        assert_eq!((error.line, error.column), (2, 1));
+        assert!(error.context.is_empty());
+        assert!(error.expected.contains(&"`SECTIONS`".to_string()));
+    }
+
+    #[test]
+    fn test_display() {
+        let error = ParseError {
+            offset: 10,
+            line: 2,
+            column: 4,
+            context: vec!["output section `.data`".into(), "SECTIONS".into()],
+            expected: vec!["`}`".into(), "symbol".into()],
+        };
+        assert_eq!(
+            error.to_string(),
+            "line 2, column 4: expected one of `}`, symbol, in output section `.data`, in SECTIONS"
+        );
+    }
+
+    #[test]
+    fn test_snippet() {
+        let source = "SECTIONS {\n  .a : { *(.a) ?? }\n}";
+        let error = ParseError {
+            offset: 26,
+            line: 2,
+            column: 16,
+            context: vec![],
+            expected: vec![],
+        };
+        assert_eq!(
+            error.snippet(source),
+            format!(
+                " --> 2:16\n  |\n2 |   .a : {{ *(.a) ?? }}\n  | {}^^\n",
+                " ".repeat(15)
+            )
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use super::expressions::{BinaryOperator, Expression, UnaryOperator};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Values that an expression can refer to. Only symbols are required, the
+/// other lookups report everything as unknown by default.
+pub trait Environment {
+    /// Value of a symbol, including `.` for the location counter
+    fn symbol(&self, name: &str) -> Option<u64>;
+
+    /// Origin of a memory region, for `ORIGIN()`
+    fn region_origin(&self, _region: &str) -> Option<u64> {
+        None
+    }
+
+    /// Length of a memory region, for `LENGTH()`
+    fn region_length(&self, _region: &str) -> Option<u64> {
+        None
+    }
+
+    /// Address of an output section, for `ADDR()`
+    fn section_address(&self, _section: &str) -> Option<u64> {
+        None
+    }
+
+    /// Load address of an output section, for `LOADADDR()`
+    fn section_load_address(&self, _section: &str) -> Option<u64> {
+        None
+    }
+
+    /// Size of an output section, for `SIZEOF()`
+    fn section_size(&self, _section: &str) -> Option<u64> {
+        None
+    }
+
+    /// Value of `MAXPAGESIZE` or `COMMONPAGESIZE`, for `CONSTANT()`
+    fn constant(&self, _name: &str) -> Option<u64> {
+        None
+    }
+
+    /// Start of a segment given with `-T`, for `SEGMENT_START()`
+    fn segment_start(&self, _segment: &str) -> Option<u64> {
+        None
+    }
+}
+
+/// Environment without any symbol, region or section.
+impl Environment for () {
+    fn symbol(&self, _name: &str) -> Option<u64> {
+        None
+    }
+}
+
+/// Symbol table, without regions or sections.
+impl Environment for HashMap<String, u64> {
+    fn symbol(&self, name: &str) -> Option<u64> {
+        self.get(name).copied()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EvalError {
+    UnknownSymbol(String),
+    UnknownRegion(String),
+    UnknownSection(String),
+    UnknownConstant(String),
+    UnknownFunction(String),
+    /// A builtin was called with the wrong number or kind of arguments
+    InvalidArguments(String),
+    DivisionByZero,
+    Overflow,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::UnknownSymbol(name) => write!(f, "unknown symbol `{}`", name),
+            EvalError::UnknownRegion(name) => write!(f, "unknown memory region `{}`", name),
+            EvalError::UnknownSection(name) => write!(f, "unknown section `{}`", name),
+            EvalError::UnknownConstant(name) => write!(f, "unknown constant `{}`", name),
+            EvalError::UnknownFunction(name) => write!(f, "unknown function `{}`", name),
+            EvalError::InvalidArguments(name) => write!(f, "invalid arguments to `{}`", name),
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::Overflow => write!(f, "arithmetic overflow"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl Expression {
+    /// Computes the value of the expression, looking up symbols, regions and
+    /// sections in `env`.
+    pub fn evaluate<E: Environment + ?Sized>(&self, env: &E) -> Result<u64, EvalError> {
+        match self {
+            Expression::Number(value) => Ok(*value),
+            Expression::Ident(name) => env
+                .symbol(name)
+                .ok_or_else(|| EvalError::UnknownSymbol(name.clone())),
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => call(function, arguments, env),
+            Expression::UnaryOp {
+                operator, right, ..
+            } => {
+                let right = right.evaluate(env)?;
+                Ok(match operator {
+                    UnaryOperator::LogicNot => (right == 0) as u64,
+                    // Addresses are unsigned, negation wraps as in ld
+                    UnaryOperator::Minus => right.wrapping_neg(),
+                    UnaryOperator::BitwiseNot => !right,
+                })
+            }
+            Expression::BinaryOp {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = left.evaluate(env)?;
+                // Logical operators don't evaluate the right side when the
+                // left one decides the result
+                match operator {
+                    BinaryOperator::LogicOr if left != 0 => return Ok(1),
+                    BinaryOperator::LogicAnd if left == 0 => return Ok(0),
+                    _ => {}
+                }
+                let right = right.evaluate(env)?;
+                binary(operator, left, right)
+            }
+            Expression::TernaryOp {
+                condition,
+                left,
+                right,
+                ..
+            } => {
+                if condition.evaluate(env)? != 0 {
+                    left.evaluate(env)
+                } else {
+                    right.evaluate(env)
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn binary(operator: &BinaryOperator, left: u64, right: u64) -> Result<u64, EvalError> {
+    use BinaryOperator::*;
+    let shift = || u32::try_from(right).map_err(|_| EvalError::Overflow);
+    match operator {
+        LogicOr => Ok((left != 0 || right != 0) as u64),
+        LogicAnd => Ok((left != 0 && right != 0) as u64),
+        BitwiseOr => Ok(left | right),
+        BitwiseAnd => Ok(left & right),
+        Equals => Ok((left == right) as u64),
+        NotEquals => Ok((left != right) as u64),
+        Lesser => Ok((left < right) as u64),
+        Greater => Ok((left > right) as u64),
+        LesserOrEquals => Ok((left <= right) as u64),
+        GreaterOrEquals => Ok((left >= right) as u64),
+        ShiftRight => left.checked_shr(shift()?).ok_or(EvalError::Overflow),
+        ShiftLeft => left.checked_shl(shift()?).ok_or(EvalError::Overflow),
+        // Arithmetic wraps around like negation
+        Plus => Ok(left.wrapping_add(right)),
+        Minus => Ok(left.wrapping_sub(right)),
+        Multiply => Ok(left.wrapping_mul(right)),
+        Divide => left.checked_div(right).ok_or(EvalError::DivisionByZero),
+        Remainder => left.checked_rem(right).ok_or(EvalError::DivisionByZero),
+    }
+}
+
+fn call<E: Environment + ?Sized>(
+    function: &str,
+    arguments: &[Expression],
+    env: &E,
+) -> Result<u64, EvalError> {
+    let invalid = || EvalError::InvalidArguments(function.to_string());
+    // Builtins taking a region, section or constant name
+    let name = || match arguments {
+        [Expression::Ident(name)] => Ok(name.as_str()),
+        _ => Err(invalid()),
+    };
+    let values = || {
+        arguments
+            .iter()
+            .map(|argument| argument.evaluate(env))
+            .collect::<Result<Vec<u64>, EvalError>>()
+    };
+    match function {
+        "ALIGN" => match values()?.as_slice() {
+            [align] => align_up(
+                env.symbol(".")
+                    .ok_or_else(|| EvalError::UnknownSymbol(".".into()))?,
+                *align,
+            ),
+            [value, align] => align_up(*value, *align),
+            _ => Err(invalid()),
+        },
+        "MAX" | "MIN" => match values()?.as_slice() {
+            [a, b] if function == "MAX" => Ok(*a.max(b)),
+            [a, b] => Ok(*a.min(b)),
+            _ => Err(invalid()),
+        },
+        "ABSOLUTE" => match values()?.as_slice() {
+            [value] => Ok(*value),
+            _ => Err(invalid()),
+        },
+        "LOG2CEIL" => match values()?.as_slice() {
+            // LOG2CEIL(0) is 0 in ld
+            [0] | [1] => Ok(0),
+            [value] => Ok(u64::from(64 - (value - 1).leading_zeros())),
+            _ => Err(invalid()),
+        },
+        "DEFINED" => Ok(env.symbol(name()?).is_some() as u64),
+        "ORIGIN" => {
+            let region = name()?;
+            env.region_origin(region)
+                .ok_or_else(|| EvalError::UnknownRegion(region.into()))
+        }
+        "LENGTH" => {
+            let region = name()?;
+            env.region_length(region)
+                .ok_or_else(|| EvalError::UnknownRegion(region.into()))
+        }
+        "ADDR" => {
+            let section = name()?;
+            env.section_address(section)
+                .ok_or_else(|| EvalError::UnknownSection(section.into()))
+        }
+        "LOADADDR" => {
+            let section = name()?;
+            env.section_load_address(section)
+                .ok_or_else(|| EvalError::UnknownSection(section.into()))
+        }
+        "SIZEOF" => {
+            let section = name()?;
+            env.section_size(section)
+                .ok_or_else(|| EvalError::UnknownSection(section.into()))
+        }
+        "CONSTANT" => {
+            let constant = name()?;
+            env.constant(constant)
+                .ok_or_else(|| EvalError::UnknownConstant(constant.into()))
+        }
+        "SEGMENT_START" => match arguments {
+            [Expression::Ident(segment), default] => match env.segment_start(segment) {
+                Some(start) => Ok(start),
+                None => default.evaluate(env),
+            },
+            _ => Err(invalid()),
+        },
+        _ => Err(EvalError::UnknownFunction(function.to_string())),
+    }
+}
+
+pub(crate) fn align_up(value: u64, align: u64) -> Result<u64, EvalError> {
+    if align <= 1 {
+        return Ok(value);
+    }
+    let aligned = value.checked_add(align - 1).ok_or(EvalError::Overflow)?;
+    Ok(aligned - aligned % align)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::*;
+    use crate::expressions::expression;
+
+    struct TestEnvironment;
+
+    impl Environment for TestEnvironment {
+        fn symbol(&self, name: &str) -> Option<u64> {
+            match name {
+                "." => Some(0x1001),
+                "_stack_size" => Some(0x400),
+                _ => None,
+            }
+        }
+
+        fn region_origin(&self, region: &str) -> Option<u64> {
+            (region == "RAM").then_some(0x2000_0000)
+        }
+
+        fn region_length(&self, region: &str) -> Option<u64> {
+            (region == "RAM").then_some(0x8000)
+        }
+
+        fn section_address(&self, section: &str) -> Option<u64> {
+            (section == ".text").then_some(0x100)
+        }
+
+        fn section_load_address(&self, section: &str) -> Option<u64> {
+            (section == ".data").then_some(0x800)
+        }
+
+        fn section_size(&self, section: &str) -> Option<u64> {
+            (section == ".text").then_some(0x80)
+        }
+
+        fn constant(&self, name: &str) -> Option<u64> {
+            (name == "MAXPAGESIZE").then_some(0x1000)
+        }
+    }
+
+    fn eval(input: &str) -> Result<u64, EvalError> {
+        expression(input).unwrap().1.evaluate(&TestEnvironment)
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("17 % 5 - 10 / 4"), Ok(0));
+        assert_eq!(eval("1 << 4 | 1 >> 1"), Ok(16));
+        assert_eq!(eval("0xF0 & ~0x30"), Ok(0xC0));
+        assert_eq!(eval("-1"), Ok(u64::MAX));
+        assert_eq!(eval("0 - 1"), Ok(u64::MAX));
+        assert_eq!(eval("0xFFFFFFFFFFFFFFFF + 2"), Ok(1));
+        assert_eq!(eval("0x8000000000000000 * 2"), Ok(0));
+        assert_eq!(eval("!0 && 2 < 3 && 3 <= 3 && 4 > 3 && 4 >= 4"), Ok(1));
+        assert_eq!(eval("1 == 2 || 1 != 1"), Ok(0));
+        assert_eq!(eval("0 ? 1 : 2 ? 3 : 4"), Ok(3));
+        assert_eq!(eval(". + _stack_size"), Ok(0x1401));
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(eval("ALIGN(8)"), Ok(0x1008));
+        assert_eq!(eval("ALIGN(0x1234, 0x100)"), Ok(0x1300));
+        assert_eq!(eval("MAX(1, 2) + MIN(3, 4)"), Ok(5));
+        assert_eq!(eval("ABSOLUTE(7)"), Ok(7));
+        assert_eq!(eval("ORIGIN(RAM) + LENGTH(RAM)"), Ok(0x2000_8000));
+        assert_eq!(eval("ADDR(.text) + SIZEOF(.text)"), Ok(0x180));
+        assert_eq!(eval("LOADADDR(.data)"), Ok(0x800));
+        assert_eq!(eval("DEFINED(_stack_size) + DEFINED(_heap_size)"), Ok(1));
+        assert_eq!(eval("CONSTANT(MAXPAGESIZE)"), Ok(0x1000));
+        assert_eq!(eval("SEGMENT_START(text, 0x400000)"), Ok(0x400000));
+        assert_eq!(eval("LOG2CEIL(0)"), Ok(0));
+        assert_eq!(eval("LOG2CEIL(5)"), Ok(3));
+        assert_eq!(eval("LOG2CEIL(8)"), Ok(3));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            eval("_heap_size"),
+            Err(EvalError::UnknownSymbol("_heap_size".into()))
+        );
+        assert_eq!(
+            eval("ORIGIN(ROM)"),
+            Err(EvalError::UnknownRegion("ROM".into()))
+        );
+        assert_eq!(
+            eval("SIZEOF(.bss)"),
+            Err(EvalError::UnknownSection(".bss".into()))
+        );
+        assert_eq!(
+            eval("CONSTANT(COMMONPAGESIZE)"),
+            Err(EvalError::UnknownConstant("COMMONPAGESIZE".into()))
+        );
+        assert_eq!(
+            eval("FOO(1)"),
+            Err(EvalError::UnknownFunction("FOO".into()))
+        );
+        assert_eq!(
+            eval("MAX(1)"),
+            Err(EvalError::InvalidArguments("MAX".into()))
+        );
+        assert_eq!(
+            eval("ORIGIN(1)"),
+            Err(EvalError::InvalidArguments("ORIGIN".into()))
+        );
+        assert_eq!(eval("1 / (2 - 2)"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("1 % 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("1 << 64"), Err(EvalError::Overflow));
+        assert_eq!(eval("1 || _heap_size"), Ok(1));
+        assert_eq!(eval("0 && 1 / 0"), Ok(0));
+    }
+}
@@ -1,13 +1,13 @@
+use super::error::{expected, tag, IResult};
 use super::idents::symbol;
 use super::numbers::number;
+use super::span::SourceSpan;
 use super::whitespace::opt_space;
 use nom::{
     branch::alt,
-    bytes::complete::tag,
     combinator::map,
     multi::{fold_many0, separated_list0},
-    sequence::{delimited, pair},
-    IResult,
+    sequence::{delimited, pair, tuple},
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -40,28 +40,71 @@ pub enum BinaryOperator {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
+    /// Has no span, see `Spanned`
     Ident(String),
+    /// Has no span, see `Spanned`
     Number(u64),
     Call {
         function: String,
         arguments: Vec<Expression>,
+        span: SourceSpan,
     },
     UnaryOp {
         operator: UnaryOperator,
         right: Box<Expression>,
+        span: SourceSpan,
     },
     BinaryOp {
         left: Box<Expression>,
         operator: BinaryOperator,
         right: Box<Expression>,
+        span: SourceSpan,
     },
     TernaryOp {
         condition: Box<Expression>,
         left: Box<Expression>,
         right: Box<Expression>,
+        span: SourceSpan,
     },
 }
 
+impl BinaryOperator {
+    /// Precedence level of the operator, matching the `expr_level_N` parser
+    /// that reads it. Lower levels bind tighter.
+    pub(crate) fn precedence(&self) -> u8 {
+        use BinaryOperator::*;
+        match self {
+            Multiply | Divide | Remainder => 2,
+            Plus | Minus => 3,
+            ShiftLeft | ShiftRight => 4,
+            Equals | NotEquals | Lesser | Greater | LesserOrEquals | GreaterOrEquals => 5,
+            BitwiseAnd => 6,
+            BitwiseOr => 7,
+            LogicAnd => 8,
+            LogicOr => 9,
+        }
+    }
+}
+
+impl Expression {
+    /// Precedence level of the expression: 1 for values and unary operations,
+    /// the operator level for binary operations and 10 for the ternary
+    /// operator.
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Expression::BinaryOp { operator, .. } => operator.precedence(),
+            Expression::TernaryOp { .. } => 10,
+            _ => 1,
+        }
+    }
+
+    /// Folds the expression to a number if it doesn't depend on any symbol,
+    /// region or section.
+    pub fn constant_value(&self) -> Option<u64> {
+        self.evaluate(&()).ok()
+    }
+}
+
 fn value_ident(input: &str) -> IResult<&str, Expression> {
     map(symbol, |x: &str| Expression::Ident(x.into()))(input)
 }
@@ -71,7 +114,8 @@ fn value_number(input: &str) -> IResult<&str, Expression> {
 }
 
 fn value_nested(input: &str) -> IResult<&str, Expression> {
-    delimited(tag("("), wsc!(expression), tag(")"))(input)
+    // The span of a nested expression covers its parentheses
+    spanned_expression(delimited(tag("("), wsc!(expression), tag(")")))(input)
 }
 
 fn value_call(input: &str) -> IResult<&str, Expression> {
@@ -84,12 +128,18 @@ fn value_call(input: &str) -> IResult<&str, Expression> {
         Expression::Call {
             function: func.into(),
             arguments: args,
+            span: SourceSpan::default(),
         },
     ))
 }
 
 pub fn value(input: &str) -> IResult<&str, Expression> {
-    alt((value_nested, value_call, value_number, value_ident))(input)
+    alt((
+        value_nested,
+        spanned_expression(value_call),
+        value_number,
+        value_ident,
+    ))(input)
 }
 
 fn expr_unary_op(input: &str) -> IResult<&str, Expression> {
@@ -106,73 +156,130 @@ fn expr_unary_op(input: &str) -> IResult<&str, Expression> {
                 _ => panic!("Invalid operator"),
             },
             right: Box::new(right),
+            span: SourceSpan::default(),
         },
     ))
 }
 
 fn expr_level_1(input: &str) -> IResult<&str, Expression> {
-    alt((expr_unary_op, value))(input)
+    alt((spanned_expression(expr_unary_op), value))(input)
+}
+
+/// Like `spanned`, except that identifiers and numbers have no span to set
+fn spanned_expression<'a>(
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, Expression>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Expression> {
+    move |input: &'a str| {
+        let (rest, mut expression) = parser(input)?;
+        if let Some(span) = expression.span_mut() {
+            *span = SourceSpan::consumed(input, rest);
+        }
+        Ok((rest, expression))
+    }
+}
+
+/// Returns the remaining input without consuming it, to find where an
+/// operand ends
+fn remaining(input: &str) -> IResult<&str, &str> {
+    Ok((input, input))
+}
+
+/// Builds the operation spanning from `start`, where its left operand begins,
+/// to `rest`, where its right operand ends
+fn binary_op(
+    start: &str,
+    left: Expression,
+    operator: BinaryOperator,
+    right: Expression,
+    rest: &str,
+) -> Expression {
+    Expression::BinaryOp {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+        span: SourceSpan::consumed(start, rest),
+    }
 }
 
 fn expr_level_2(input: &str) -> IResult<&str, Expression> {
+    let start = input;
     let (input, first) = expr_level_1(input)?;
     let (input, fold) = fold_many0(
-        pair(wsc!(alt((tag("*"), tag("/"), tag("%")))), expr_level_1),
+        tuple((
+            wsc!(alt((tag("*"), tag("/"), tag("%")))),
+            expr_level_1,
+            remaining,
+        )),
         || first.clone(),
-        |prev, new: (&str, Expression)| Expression::BinaryOp {
-            left: Box::new(prev),
-            operator: match new.0 {
-                "*" => BinaryOperator::Multiply,
-                "/" => BinaryOperator::Divide,
-                "%" => BinaryOperator::Remainder,
-                _ => panic!("Invalid operator"),
-            },
-            right: Box::new(new.1),
+        |prev, (operator, right, rest)| {
+            binary_op(
+                start,
+                prev,
+                match operator {
+                    "*" => BinaryOperator::Multiply,
+                    "/" => BinaryOperator::Divide,
+                    "%" => BinaryOperator::Remainder,
+                    _ => panic!("Invalid operator"),
+                },
+                right,
+                rest,
+            )
         },
     )(input)?;
     Ok((input, fold))
 }
 
 fn expr_level_3(input: &str) -> IResult<&str, Expression> {
+    let start = input;
     let (input, first) = expr_level_2(input)?;
     let (input, fold) = fold_many0(
-        pair(wsc!(alt((tag("+"), tag("-")))), expr_level_2),
+        tuple((wsc!(alt((tag("+"), tag("-")))), expr_level_2, remaining)),
         || first.clone(),
-        |prev, new: (&str, Expression)| Expression::BinaryOp {
-            left: Box::new(prev),
-            operator: match new.0 {
-                "+" => BinaryOperator::Plus,
-                "-" => BinaryOperator::Minus,
-                _ => panic!("Invalid operator"),
-            },
-            right: Box::new(new.1),
+        |prev, (operator, right, rest)| {
+            binary_op(
+                start,
+                prev,
+                match operator {
+                    "+" => BinaryOperator::Plus,
+                    "-" => BinaryOperator::Minus,
+                    _ => panic!("Invalid operator"),
+                },
+                right,
+                rest,
+            )
         },
     )(input)?;
     Ok((input, fold))
 }
 
 fn expr_level_4(input: &str) -> IResult<&str, Expression> {
+    let start = input;
     let (input, first) = expr_level_3(input)?;
     let (input, fold) = fold_many0(
-        pair(wsc!(alt((tag("<<"), tag(">>")))), expr_level_3),
+        tuple((wsc!(alt((tag("<<"), tag(">>")))), expr_level_3, remaining)),
         || first.clone(),
-        |prev, new: (&str, Expression)| Expression::BinaryOp {
-            left: Box::new(prev),
-            operator: match new.0 {
-                "<<" => BinaryOperator::ShiftLeft,
-                ">>" => BinaryOperator::ShiftRight,
-                _ => panic!("Invalid operator"),
-            },
-            right: Box::new(new.1),
+        |prev, (operator, right, rest)| {
+            binary_op(
+                start,
+                prev,
+                match operator {
+                    "<<" => BinaryOperator::ShiftLeft,
+                    ">>" => BinaryOperator::ShiftRight,
+                    _ => panic!("Invalid operator"),
+                },
+                right,
+                rest,
+            )
         },
     )(input)?;
     Ok((input, fold))
 }
 
 fn expr_level_5(input: &str) -> IResult<&str, Expression> {
+    let start = input;
     let (input, first) = expr_level_4(input)?;
     let (input, fold) = fold_many0(
-        pair(
+        tuple((
             wsc!(alt((
                 tag("=="),
                 tag("!="),
@@ -182,77 +289,70 @@ fn expr_level_5(input: &str) -> IResult<&str, Expression> {
                 tag(">")
             ))),
             expr_level_4,
-        ),
+            remaining,
+        )),
         || first.clone(),
-        |prev, new: (&str, Expression)| Expression::BinaryOp {
-            left: Box::new(prev),
-            operator: match new.0 {
-                "==" => BinaryOperator::Equals,
-                "!=" => BinaryOperator::NotEquals,
-                "<=" => BinaryOperator::LesserOrEquals,
-                ">=" => BinaryOperator::GreaterOrEquals,
-                "<" => BinaryOperator::Lesser,
-                ">" => BinaryOperator::Greater,
-                _ => panic!("Invalid operator"),
-            },
-            right: Box::new(new.1),
+        |prev, (operator, right, rest)| {
+            binary_op(
+                start,
+                prev,
+                match operator {
+                    "==" => BinaryOperator::Equals,
+                    "!=" => BinaryOperator::NotEquals,
+                    "<=" => BinaryOperator::LesserOrEquals,
+                    ">=" => BinaryOperator::GreaterOrEquals,
+                    "<" => BinaryOperator::Lesser,
+                    ">" => BinaryOperator::Greater,
+                    _ => panic!("Invalid operator"),
+                },
+                right,
+                rest,
+            )
         },
     )(input)?;
     Ok((input, fold))
 }
 
 fn expr_level_6(input: &str) -> IResult<&str, Expression> {
+    let start = input;
     let (input, first) = expr_level_5(input)?;
     let (input, fold) = fold_many0(
-        pair(wsc!(tag("&")), expr_level_5),
+        tuple((wsc!(tag("&")), expr_level_5, remaining)),
         || first.clone(),
-        |prev, new: (&str, Expression)| Expression::BinaryOp {
-            left: Box::new(prev),
-            operator: BinaryOperator::BitwiseAnd,
-            right: Box::new(new.1),
-        },
+        |prev, (_, right, rest)| binary_op(start, prev, BinaryOperator::BitwiseAnd, right, rest),
     )(input)?;
     Ok((input, fold))
 }
 
 fn expr_level_7(input: &str) -> IResult<&str, Expression> {
+    let start = input;
     let (input, first) = expr_level_6(input)?;
     let (input, fold) = fold_many0(
-        pair(wsc!(tag("|")), expr_level_6),
+        tuple((wsc!(tag("|")), expr_level_6, remaining)),
         || first.clone(),
-        |prev, new: (&str, Expression)| Expression::BinaryOp {
-            left: Box::new(prev),
-            operator: BinaryOperator::BitwiseOr,
-            right: Box::new(new.1),
-        },
+        |prev, (_, right, rest)| binary_op(start, prev, BinaryOperator::BitwiseOr, right, rest),
     )(input)?;
     Ok((input, fold))
 }
 
 fn expr_level_8(input: &str) -> IResult<&str, Expression> {
+    let start = input;
     let (input, first) = expr_level_7(input)?;
     let (input, fold) = fold_many0(
-        pair(wsc!(tag("&&")), expr_level_7),
+        tuple((wsc!(tag("&&")), expr_level_7, remaining)),
         || first.clone(),
-        |prev, new: (&str, Expression)| Expression::BinaryOp {
-            left: Box::new(prev),
-            operator: BinaryOperator::LogicAnd,
-            right: Box::new(new.1),
-        },
+        |prev, (_, right, rest)| binary_op(start, prev, BinaryOperator::LogicAnd, right, rest),
     )(input)?;
     Ok((input, fold))
 }
 
 fn expr_level_9(input: &str) -> IResult<&str, Expression> {
+    let start = input;
     let (input, first) = expr_level_8(input)?;
     let (input, fold) = fold_many0(
-        pair(wsc!(tag("||")), expr_level_8),
+        tuple((wsc!(tag("||")), expr_level_8, remaining)),
         || first.clone(),
-        |prev, new: (&str, Expression)| Expression::BinaryOp {
-            left: Box::new(prev),
-            operator: BinaryOperator::LogicOr,
-            right: Box::new(new.1),
-        },
+        |prev, (_, right, rest)| binary_op(start, prev, BinaryOperator::LogicOr, right, rest),
     )(input)?;
     Ok((input, fold))
 }
@@ -269,12 +369,16 @@ fn expr_ternary_op(input: &str) -> IResult<&str, Expression> {
             condition: Box::new(cond),
             left: Box::new(left),
             right: Box::new(right),
+            span: SourceSpan::default(),
         },
     ))
 }
 
 pub fn expression(input: &str) -> IResult<&str, Expression> {
-    alt((expr_ternary_op, expr_level_9))(input)
+    expected(
+        "expression",
+        alt((spanned_expression(expr_ternary_op), expr_level_9)),
+    )(input)
 }
 
 #[cfg(test)]
@@ -301,6 +405,7 @@ mod tests {
                 left: Box::new(Expression::Ident("A".into())),
                 operator: BinaryOperator::Minus,
                 right: Box::new(Expression::Ident("B".into())),
+                span: SourceSpan::default(),
             }
         );
     }
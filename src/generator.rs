@@ -19,17 +19,17 @@ impl Generate for Vec<RootItem> {
                 Command(cmd) => {
                     output.push_str(&format!("{}\n", cmd.generate()));
                 }
-                Memory { regions } => {
+                Memory { commands, .. } => {
                     output.push_str("MEMORY {\n");
-                    for region in regions {
+                    for command in commands {
                         output.push_str(&format!(
                             "{}\n",
-                            indent_all_by(INDENTATION, region.generate())
+                            indent_all_by(INDENTATION, command.generate())
                         ));
                     }
                     output.push_str("}\n\n");
                 }
-                Sections { list } => {
+                Sections { list, .. } => {
                     output.push_str("SECTIONS {\n");
                     for section in list {
                         output.push_str(&format!(
@@ -39,12 +39,87 @@ impl Generate for Vec<RootItem> {
                     }
                     output.push_str("}\n\n");
                 }
+                Phdrs { headers, .. } => {
+                    output.push_str("PHDRS {\n");
+                    for header in headers {
+                        output.push_str(&format!(
+                            "{}\n",
+                            indent_all_by(INDENTATION, header.generate())
+                        ));
+                    }
+                    output.push_str("}\n\n");
+                }
+                Version { nodes, .. } => {
+                    output.push_str("VERSION {\n");
+                    for node in nodes {
+                        output.push_str(&format!(
+                            "{}\n",
+                            indent_all_by(INDENTATION, node.generate())
+                        ));
+                    }
+                    output.push_str("}\n\n");
+                }
             }
         }
         output
     }
 }
 
+impl Generate for Vec<VersionNode> {
+    fn generate(self) -> String {
+        let nodes: Vec<String> = self.into_iter().map(|node| node.generate()).collect();
+        nodes.join("\n\n") + "\n"
+    }
+}
+
+impl Generate for VersionNode {
+    fn generate(self) -> String {
+        let mut output = match self.name {
+            Some(name) => format!("{} {{\n", name),
+            None => "{\n".to_string(),
+        };
+        for (scope, entries) in [("global", self.global), ("local", self.local)] {
+            if entries.is_empty() {
+                continue;
+            }
+            output.push_str(&format!("  {}:\n", scope));
+            for entry in entries {
+                output.push_str(&format!(
+                    "{}\n",
+                    indent_all_by(2 * INDENTATION, entry.generate())
+                ));
+            }
+        }
+        output.push('}');
+        for dependency in self.dependencies {
+            output.push_str(&format!(" {}", dependency));
+        }
+        output.push(';');
+        output
+    }
+}
+
+impl Generate for VersionEntry {
+    fn generate(self) -> String {
+        use VersionEntry::*;
+        match self {
+            Pattern(name) => format!("{};", name),
+            Literal(name) => format!("\"{}\";", name),
+            Extern { language, entries } => {
+                let mut output = format!("extern \"{}\" {{\n", language);
+                for entry in entries {
+                    output.push_str(&format!(
+                        "{}\n",
+                        indent_all_by(INDENTATION, entry.generate())
+                    ));
+                }
+                output.push_str("};");
+                output
+            }
+        }
+    }
+}
+
 impl Generate for Statement {
     fn generate(self) -> String {
         use Statement::*;
@@ -53,6 +128,7 @@ impl Generate for Statement {
                 name,
                 operator,
                 expression,
+                ..
             } => {
                 format!(
                     "{} {} {};",
@@ -61,16 +137,22 @@ impl Generate for Statement {
                     expression.generate()
                 )
             }
-            Hidden { name, expression } => {
+            Hidden {
+                name, expression, ..
+            } => {
                 format!("HIDDEN ({} = {});", name, expression.generate())
             }
-            Provide { name, expression } => {
+            Provide {
+                name, expression, ..
+            } => {
                 format!("PROVIDE ({} = {});", name, expression.generate())
             }
-            ProvideHidden { name, expression } => {
+            ProvideHidden {
+                name, expression, ..
+            } => {
                 format!("PROVIDE_HIDDEN ({} = {});", name, expression.generate())
             }
-            Assert { expr, text } => {
+            Assert { expr, text, .. } => {
                 format!("ASSERT (({}), \"{}\");", expr.generate(), text)
             }
         }
@@ -97,38 +179,46 @@ impl Generate for AssignOperator {
 impl Generate for Expression {
     fn generate(self) -> String {
         match self {
-            Expression::Ident(ident) => ident.clone(),
+            Expression::Ident(ident) => quote_symbol(ident),
             Expression::Number(num) => format!("0x{num:x}"),
             Expression::Call {
                 function,
                 arguments,
+                ..
             } => {
                 let args: Vec<String> = arguments.into_iter().map(|arg| arg.generate()).collect();
                 format!("{}({})", function, args.join(", "))
             }
-            Expression::UnaryOp { operator, right } => {
-                format!("{}{}", operator.generate(), right.generate())
+            Expression::UnaryOp {
+                operator, right, ..
+            } => {
+                format!("{}{}", operator.generate(), generate_operand(*right, 1))
             }
             Expression::BinaryOp {
                 left,
                 operator,
                 right,
+                ..
             } => {
+                // Operators are left-associative, so a right operand at the
+                // same level needs parentheses
+                let level = operator.precedence();
                 format!(
                     "{} {} {}",
-                    left.generate(),
+                    generate_operand(*left, level),
                     operator.generate(),
-                    right.generate()
+                    generate_operand(*right, level - 1)
                 )
             }
             Expression::TernaryOp {
                 condition,
                 left,
                 right,
+                ..
             } => {
                 format!(
                     "{} ? {} : {}",
-                    condition.generate(),
+                    generate_operand(*condition, 9),
                     left.generate(),
                     right.generate()
                 )
@@ -137,6 +227,30 @@ impl Generate for Expression {
     }
 }
 
+/// Generates an operand, wrapping it in parentheses when it binds looser than
+/// `level`.
+fn generate_operand(expression: Expression, level: u8) -> String {
+    if expression.precedence() > level {
+        format!("({})", expression.generate())
+    } else {
+        expression.generate()
+    }
+}
+
+/// Wraps a symbol in double quotes when it can't be read back as a bare identifier.
+fn quote_symbol(name: String) -> String {
+    let mut chars = name.chars();
+    let is_simple = chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '-');
+    if is_simple {
+        name
+    } else {
+        format!("\"{}\"", name)
+    }
+}
+
 impl Generate for UnaryOperator {
     fn generate(self) -> String {
         use UnaryOperator::*;
@@ -177,32 +291,139 @@ impl Generate for Command {
     fn generate(self) -> String {
         use Command::*;
         match self {
-            Call { name, arguments } => {
+            Call {
+                name, arguments, ..
+            } => {
                 let args: Vec<String> = arguments.into_iter().map(|arg| arg.generate()).collect();
                 format!("{}({});", name, args.join(", "))
             }
-            Include { file } => format!("INCLUDE {};", file),
-            Insert { .. } => unimplemented!(),
+            Include { file, .. } => format!("INCLUDE {};", file),
+            Insert { order, section, .. } => format!("INSERT {} {};", order.generate(), section),
+            Nullary { command, .. } => format!("{};", command.generate()),
+            Input { files, .. } => format!("INPUT({});", generate_file_list(files)),
+            Group { files, .. } => format!("GROUP({});", generate_file_list(files)),
+            Extern { symbols, .. } => {
+                let symbols: Vec<String> = symbols.into_iter().map(quote_symbol).collect();
+                format!("EXTERN({});", symbols.join(" "))
+            }
+            Startup { file, .. } => format!("STARTUP({});", quote_file(file)),
+            SearchDir { path, .. } => format!("SEARCH_DIR({});", quote_file(path)),
+        }
+    }
+}
+
+impl Generate for NullaryCommand {
+    fn generate(self) -> String {
+        use NullaryCommand::*;
+        match self {
+            ForceCommonAllocation => "FORCE_COMMON_ALLOCATION".to_string(),
+            InhibitCommonAllocation => "INHIBIT_COMMON_ALLOCATION".to_string(),
+            ForceGroupAllocation => "FORCE_GROUP_ALLOCATION".to_string(),
+        }
+    }
+}
+
+impl Generate for InputFile {
+    fn generate(self) -> String {
+        match self {
+            InputFile::File(name) => quote_file(name),
+            InputFile::Library(name) => format!("-l{}", name),
+            InputFile::AsNeeded(files) => format!("AS_NEEDED({})", generate_file_list(files)),
+        }
+    }
+}
+
+fn generate_file_list(files: Vec<InputFile>) -> String {
+    let files: Vec<String> = files.into_iter().map(|file| file.generate()).collect();
+    files.join(" ")
+}
+
+/// Quotes a file name unless it reads back as a single file name token,
+/// which can't start with `=` (a sysroot prefix) or `-` (as in `-lname`)
+fn quote_file(name: String) -> String {
+    let mut chars = name.chars();
+    let is_simple = chars
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || "_./\\$~+:".contains(c))
+        && chars.all(|c| c.is_alphanumeric() || "_./\\$~+=:-".contains(c));
+    if is_simple {
+        name
+    } else {
+        format!("\"{}\"", name)
+    }
+}
+
+impl Generate for InsertOrder {
+    fn generate(self) -> String {
+        match self {
+            InsertOrder::Before => "BEFORE".to_string(),
+            InsertOrder::After => "AFTER".to_string(),
+        }
+    }
+}
+
+impl Generate for MemoryCommand {
+    fn generate(self) -> String {
+        match self {
+            MemoryCommand::Region(region) => region.generate(),
+            MemoryCommand::Include { file, .. } => format!("INCLUDE {}", file),
         }
     }
 }
 
 impl Generate for Region {
     fn generate(self) -> String {
-        let length = if self.length % (1024 * 1024) == 0 {
-            format!("{}M", self.length / (1024 * 1024))
-        } else if self.length % 1024 == 0 {
-            format!("{}K", self.length / 1024)
+        let origin = match self.origin {
+            Expression::Number(origin) => format!("0x{:X}", origin),
+            origin => origin.generate(),
+        };
+        let length = match self.length {
+            Expression::Number(length) if length % (1024 * 1024) == 0 => {
+                format!("{}M", length / (1024 * 1024))
+            }
+            Expression::Number(length) if length % 1024 == 0 => {
+                format!("{}K", length / 1024)
+            }
+            Expression::Number(length) => length.to_string(),
+            length => length.generate(),
+        };
+        let attributes = if self.attributes == RegionAttributes::default() {
+            String::new()
         } else {
-            self.length.to_string()
+            format!(" ({})", self.attributes.generate())
         };
         format!(
-            "{} : ORIGIN = 0x{:X}, LENGTH = {length}",
-            self.name, self.origin
+            "{}{attributes} : ORIGIN = {origin}, LENGTH = {length}",
+            self.name
         )
     }
 }
 
+impl Generate for RegionAttributes {
+    fn generate(self) -> String {
+        let mut output = generate_region_flags(self.flags);
+        if !self.inverted.is_empty() {
+            output.push('!');
+            output.push_str(&generate_region_flags(self.inverted));
+        }
+        output
+    }
+}
+
+fn generate_region_flags(flags: RegionFlags) -> String {
+    [
+        (RegionFlags::READ, 'r'),
+        (RegionFlags::WRITE, 'w'),
+        (RegionFlags::EXECUTE, 'x'),
+        (RegionFlags::ALLOCATE, 'a'),
+        (RegionFlags::INITIALIZED, 'i'),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.contains(*flag))
+    .map(|(_, c)| c)
+    .collect()
+}
+
 impl Generate for SectionCommand {
     fn generate(self) -> String {
         match self {
@@ -220,7 +441,10 @@ impl Generate for SectionCommand {
                 content,
                 region,
                 lma_region,
+                phdrs,
                 fillexp,
+                fill_pattern,
+                ..
             }) => {
                 let mut output = format!("{} ", name);
                 if let Some(vma_address) = vma_address {
@@ -231,19 +455,19 @@ impl Generate for SectionCommand {
                 }
                 output.push(':');
                 if let Some(lma_address) = lma_address {
-                    output.push_str(&format!(" AT({}),", lma_address.generate()));
+                    output.push_str(&format!(" AT({})", lma_address.generate()));
                 }
                 if let Some(section_align) = section_align {
-                    output.push_str(&format!(" ALIGN({}),", section_align.generate()));
+                    output.push_str(&format!(" ALIGN({})", section_align.generate()));
                 }
                 if align_with_input {
-                    output.push_str(" ALIGN_WITH_INPUT,");
+                    output.push_str(" ALIGN_WITH_INPUT");
                 }
                 if let Some(subsection_align) = subsection_align {
-                    output.push_str(&format!(" SUBALIGN({}),", subsection_align.generate()));
+                    output.push_str(&format!(" SUBALIGN({})", subsection_align.generate()));
                 }
                 if let Some(constraint) = constraint {
-                    output.push_str(&format!(" {},", constraint.generate()));
+                    output.push_str(&format!(" {}", constraint.generate()));
                 }
                 output.push_str(" {\n");
                 for cmd in content {
@@ -251,20 +475,95 @@ impl Generate for SectionCommand {
                 }
                 output.push('}');
                 if let Some(region) = region {
-                    output.push_str(&format!(" >{},", region));
+                    output.push_str(&format!(" >{}", region));
                 }
                 if let Some(lma_region) = lma_region {
-                    output.push_str(&format!(" AT>{}:", lma_region));
+                    output.push_str(&format!(" AT>{}", lma_region));
                 }
-                if let Some(fillexp) = fillexp {
-                    output.push_str(&format!(" ={};", fillexp.generate()));
+                for phdr in phdrs {
+                    output.push_str(&format!(" :{}", phdr));
                 }
+                output.push_str(&generate_fill(fillexp, fill_pattern));
                 output
             }
+            SectionCommand::Overlay(overlay) => overlay.generate(),
         }
     }
 }
 
+impl Generate for Overlay {
+    fn generate(self) -> String {
+        let mut output = String::from("OVERLAY ");
+        if let Some(start) = self.start {
+            output.push_str(&format!("{} ", start.generate()));
+        }
+        output.push(':');
+        if self.nocrossrefs {
+            output.push_str(" NOCROSSREFS");
+        }
+        if let Some(lma_address) = self.lma_address {
+            output.push_str(&format!(" AT({})", lma_address.generate()));
+        }
+        output.push_str(" {\n");
+        for section in self.sections {
+            output.push_str(&format!(
+                "{}\n",
+                indent_all_by(INDENTATION, section.generate())
+            ));
+        }
+        output.push('}');
+        if let Some(region) = self.region {
+            output.push_str(&format!(" >{}", region));
+        }
+        if let Some(lma_region) = self.lma_region {
+            output.push_str(&format!(" AT>{}", lma_region));
+        }
+        for phdr in self.phdrs {
+            output.push_str(&format!(" :{}", phdr));
+        }
+        output.push_str(&generate_fill(self.fillexp, self.fill_pattern));
+        output
+    }
+}
+
+impl Generate for OverlaySection {
+    fn generate(self) -> String {
+        let mut output = format!("{} {{\n", self.name);
+        for cmd in self.content {
+            output.push_str(&format!("  {}\n", cmd.generate()));
+        }
+        output.push('}');
+        for phdr in self.phdrs {
+            output.push_str(&format!(" :{}", phdr));
+        }
+        output.push_str(&generate_fill(self.fillexp, self.fill_pattern));
+        output
+    }
+}
+
+/// A bare hex number would read back as a byte pattern, so plain numbers are
+/// written in decimal
+fn generate_fill_expression(expr: Expression) -> String {
+    match expr {
+        Expression::Number(num) => num.to_string(),
+        expr => expr.generate(),
+    }
+}
+
+fn generate_fill_pattern(pattern: &[u8]) -> String {
+    let digits: Vec<String> = pattern.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", digits.concat())
+}
+
+/// The trailing `=fillexp` of an output section, overlay or overlay section
+fn generate_fill(fillexp: Option<Box<Expression>>, fill_pattern: Option<Vec<u8>>) -> String {
+    match (fillexp, fill_pattern) {
+        (_, Some(pattern)) => format!(" ={}", generate_fill_pattern(&pattern)),
+        (Some(fillexp), None) => format!(" ={}", generate_fill_expression(*fillexp)),
+        (None, None) => String::new(),
+    }
+}
+
 impl Generate for OutputSectionType {
     fn generate(self) -> String {
         use OutputSectionType::*;
@@ -274,6 +573,9 @@ impl Generate for OutputSectionType {
             Copy => "(COPY)".to_string(),
             Info => "(INFO)".to_string(),
             Overlay => "(OVERLAY)".to_string(),
+            ReadOnly => "(READONLY)".to_string(),
+            Type(expr) => format!("(TYPE = {})", expr.generate()),
+            ReadOnlyType(expr) => format!("(READONLY (TYPE = {}))", expr.generate()),
         }
     }
 }
@@ -293,20 +595,58 @@ impl Generate for OutputSectionCommand {
         use OutputSectionCommand::*;
         match self {
             Statement(stmt) => stmt.generate(),
-            Fill { expr } => format!("FILL({})", expr.generate()),
-            Data { d_type, value } => format!("{} {}", d_type.generate(), value.generate()),
-            InputSection { file, sections } => {
-                let sections: Vec<String> = sections.into_iter().map(|s| s.generate()).collect();
-                format!("{}({})", file.generate(), sections.join(", "))
-            }
-            KeepInputSection { file, sections } => {
-                let sections: Vec<String> = sections.into_iter().map(|s| s.generate()).collect();
-                format!("KEEP ({}({}))", file.generate(), sections.join(", "))
-            }
+            Fill { expr, .. } => format!("FILL({})", generate_fill_expression(*expr)),
+            FillPattern { pattern, .. } => format!("FILL({})", generate_fill_pattern(&pattern)),
+            Data { d_type, value, .. } => format!("{}({})", d_type.generate(), value.generate()),
+            InputSection {
+                flags,
+                file,
+                sections,
+                ..
+            } => generate_input_section(flags, file, sections),
+            KeepInputSection {
+                flags,
+                file,
+                sections,
+                ..
+            } => format!("KEEP ({})", generate_input_section(flags, file, sections)),
+            Include { file, .. } => format!("INCLUDE {}", file),
+            Asciz { string, .. } => format!("ASCIZ \"{}\"", string),
+            LinkerVersion { .. } => "LINKER_VERSION".to_string(),
+            Constructors { sorted: false, .. } => "CONSTRUCTORS".to_string(),
+            Constructors { sorted: true, .. } => "SORT(CONSTRUCTORS)".to_string(),
+            CreateObjectSymbols { .. } => "CREATE_OBJECT_SYMBOLS".to_string(),
         }
     }
 }
 
+fn generate_input_section(
+    flags: Option<InputSectionFlags>,
+    file: SectionPattern,
+    sections: Vec<SectionPattern>,
+) -> String {
+    let flags = match flags {
+        Some(flags) => format!("{} ", flags.generate()),
+        None => String::new(),
+    };
+    if sections.is_empty() {
+        return format!("{}{}", flags, file.generate());
+    }
+    let sections: Vec<String> = sections.into_iter().map(|s| s.generate()).collect();
+    format!("{}{}({})", flags, file.generate(), sections.join(" "))
+}
+
+impl Generate for InputSectionFlags {
+    fn generate(self) -> String {
+        let flags: Vec<String> = self
+            .required
+            .into_iter()
+            .chain(self.excluded.into_iter().map(|flag| format!("!{}", flag)))
+            .collect();
+        format!("INPUT_SECTION_FLAGS({})", flags.join(" & "))
+    }
+}
+
 impl Generate for DataType {
     fn generate(self) -> String {
         use DataType::*;
@@ -315,6 +655,7 @@ impl Generate for DataType {
             Short => "SHORT".to_string(),
             Long => "LONG".to_string(),
             Quad => "QUAD".to_string(),
+            SQuad => "SQUAD".to_string(),
         }
     }
 }
@@ -324,10 +665,11 @@ impl Generate for SectionPattern {
         use SectionPattern::*;
         match self {
             Simple(name) => name.clone(),
-            SortByName(name) => format!("SORT_BY_NAME({})", name),
-            SortByAlignment(name) => format!("SORT_BY_ALIGNMENT({})", name),
-            SortByInitPriority(name) => format!("SORT_BY_INIT_PRIORITY({})", name),
-            SortNone(name) => format!("SORT_NONE({})", name),
+            SortByName(inner) => format!("SORT_BY_NAME({})", inner.generate()),
+            SortByAlignment(inner) => format!("SORT_BY_ALIGNMENT({})", inner.generate()),
+            SortByInitPriority(inner) => format!("SORT_BY_INIT_PRIORITY({})", inner.generate()),
+            SortNone(inner) => format!("SORT_NONE({})", inner.generate()),
+            Reverse(inner) => format!("REVERSE({})", inner.generate()),
             ExcludeFile { files, pattern } => {
                 format!("EXCLUDE_FILE({}) {}", files.join(" "), pattern.generate())
             }
@@ -335,14 +677,221 @@ impl Generate for SectionPattern {
     }
 }
 
+impl Generate for ProgramHeader {
+    fn generate(self) -> String {
+        let mut output = format!("{} {}", self.name, self.p_type.generate());
+        if self.filehdr {
+            output.push_str(" FILEHDR");
+        }
+        if self.phdrs {
+            output.push_str(" PHDRS");
+        }
+        if let Some(at) = self.at {
+            output.push_str(&format!(" AT({})", at.generate()));
+        }
+        if let Some(flags) = self.flags {
+            output.push_str(&format!(" FLAGS({})", flags.generate()));
+        }
+        output.push(';');
+        output
+    }
+}
+
+impl Generate for ProgramHeaderType {
+    fn generate(self) -> String {
+        use ProgramHeaderType::*;
+        match self {
+            Null => "PT_NULL".to_string(),
+            Load => "PT_LOAD".to_string(),
+            Dynamic => "PT_DYNAMIC".to_string(),
+            Interp => "PT_INTERP".to_string(),
+            Note => "PT_NOTE".to_string(),
+            Shlib => "PT_SHLIB".to_string(),
+            Phdr => "PT_PHDR".to_string(),
+            Tls => "PT_TLS".to_string(),
+            GnuEhFrame => "PT_GNU_EH_FRAME".to_string(),
+            GnuStack => "PT_GNU_STACK".to_string(),
+            GnuRelro => "PT_GNU_RELRO".to_string(),
+            GnuProperty => "PT_GNU_PROPERTY".to_string(),
+            Value(expr) => expr.generate(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::expressions::expression;
     use std::{
         fs::{read_dir, File},
         io::Read,
     };
 
+    #[test]
+    fn test_version_script_round_trip() {
+        let script = "
+            VERS_1.1 {
+                global: foo1; extern \"C++\" { ns::*; \"ns::bar()\"; };
+                local: old*; original*; new*;
+            };
+            VERS_1.2 { foo2; } VERS_1.1;
+            VERS_2.0 { bar1; bar2; local: *; } VERS_1.2;
+        ";
+        let parsed_nodes = parse_version_script(script).unwrap();
+        let generated_content = parsed_nodes.clone().generate();
+        let reparsed_nodes = parse_version_script(&generated_content).unwrap();
+        assert_eq!(parsed_nodes, reparsed_nodes);
+    }
+
+    #[test]
+    fn test_expression_round_trip() {
+        for (input, value) in [
+            ("(1 + 2) * 4", Some(12)),
+            ("1 + 2 * 4", Some(9)),
+            ("10 - (4 - 3)", Some(9)),
+            ("(10 - 4) - 3", Some(3)),
+            ("64 / (4 / 2)", Some(32)),
+            ("1 << (2 + 1)", Some(8)),
+            ("-(3 - 1) & 0xF", Some(14)),
+            ("~(1 | 2) & 7", Some(4)),
+            ("(1 ? 2 : 3) ? 4 : 5", Some(4)),
+            ("0 ? 1 : 0 ? 2 : 3", Some(3)),
+            ("((1 || 0) && 0) | 6", Some(6)),
+            ("(A + B) * 4", None),
+            ("ALIGN((. + 7) & ~7)", None),
+        ] {
+            let parsed = expression(input).unwrap().1;
+            let generated = parsed.clone().generate();
+            let reparsed = expression(&generated).unwrap().1;
+            assert_eq!(parsed, reparsed, "{input} generated as {generated}");
+            assert_eq!(reparsed.constant_value(), value, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_insert_fragment() {
+        let script = builder::LinkerScriptBuilder::default()
+            .with_sections(builder::SectionBuilder::default().with_output(
+                OutputSection::new(".got").add_command(OutputSectionCommand::input_section(
+                    SectionPattern::simple("*"),
+                    [SectionPattern::simple(".got")],
+                )),
+            ))
+            .with_command(Command::insert(InsertOrder::After, ".text"))
+            .generate();
+        assert_eq!(
+            script,
+            "SECTIONS {\n  .got : {\n    *(.got)\n  }\n}\n\nINSERT AFTER .text;\n"
+        );
+        let items = parse(&script).unwrap();
+        assert_eq!(
+            items.last(),
+            Some(&RootItem::Command(Command::Insert {
+                order: InsertOrder::After,
+                section: ".text".into(),
+                span: SourceSpan::default(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_file_commands() {
+        let script = builder::LinkerScriptBuilder::default()
+            .with_command(Command::search_dir("=/usr/lib"))
+            .with_command(Command::startup("crt0.o"))
+            .with_command(Command::group([
+                InputFile::File("libc.so.6".into()),
+                InputFile::File("-lx".into()),
+                InputFile::Library("gcc".into()),
+                InputFile::AsNeeded(vec![InputFile::File("ld linux.so".into())]),
+            ]))
+            .with_command(Command::extern_symbols(["_start", "reset handler"]))
+            .with_command(Command::nullary(NullaryCommand::ForceCommonAllocation))
+            .generate();
+        assert_eq!(
+            script,
+            "SEARCH_DIR(\"=/usr/lib\");
+STARTUP(crt0.o);
+GROUP(libc.so.6 \"-lx\" -lgcc AS_NEEDED(\"ld linux.so\"));
+EXTERN(_start \"reset handler\");
+FORCE_COMMON_ALLOCATION;
+"
+        );
+        let items = parse(&script).unwrap();
+        assert_eq!(items.generate(), script);
+    }
+
+    #[test]
+    fn test_section_data() {
+        let script = builder::LinkerScriptBuilder::default()
+            .with_sections(builder::SectionBuilder::default().with_output(
+                OutputSection::new(".rodata").add_commands([
+                    OutputSectionCommand::data(DataType::SQuad, Expression::from(1)),
+                    OutputSectionCommand::asciz("built with"),
+                    OutputSectionCommand::linker_version(),
+                    OutputSectionCommand::fill_pattern([0x90; 10]),
+                    OutputSectionCommand::fill_pattern([0x90; 4]),
+                    OutputSectionCommand::fill_pattern([0x00, 0x90]),
+                    OutputSectionCommand::fill(Expression::from(0x90)),
+                    OutputSectionCommand::constructors(true),
+                    OutputSectionCommand::create_object_symbols(),
+                ]),
+            ))
+            .generate();
+        assert_eq!(
+            script,
+            "SECTIONS {
+  .rodata : {
+    SQUAD(0x1)
+    ASCIZ \"built with\"
+    LINKER_VERSION
+    FILL(0x90909090909090909090)
+    FILL(0x90909090)
+    FILL(0x0090)
+    FILL(144)
+    SORT(CONSTRUCTORS)
+    CREATE_OBJECT_SYMBOLS
+  }
+}
+
+"
+        );
+        let items = parse(&script).unwrap();
+        assert_eq!(parse(&items.clone().generate()).unwrap(), items);
+        assert_eq!(items.generate(), script);
+    }
+
+    #[test]
+    fn test_section_attributes() {
+        let generate = |section: OutputSection| SectionCommand::OutputSection(section).generate();
+        assert_eq!(
+            generate(
+                OutputSection::new(".rodata")
+                    .section_type(OutputSectionType::ReadOnlyType(Box::new(Expression::from(
+                        "SHT_PROGBITS"
+                    ))))
+                    .region("FLASH")
+            ),
+            ".rodata (READONLY (TYPE = SHT_PROGBITS)) : {\n} >FLASH"
+        );
+        assert_eq!(
+            generate(
+                OutputSection::new(".persist")
+                    .vma_address(Expression::from(0x2000))
+                    .section_type(OutputSectionType::NoLoad)
+                    .region("RAM")
+                    .lma_region("FLASH")
+                    .phdr("data")
+                    .fillexp(Expression::from(0))
+            ),
+            ".persist (0x2000) (NOLOAD) : {\n} >RAM AT>FLASH :data =0"
+        );
+        assert_eq!(
+            generate(OutputSection::new(".data").fill_pattern([0x00, 0x90])),
+            ".data : {\n} =0x0090"
+        );
+    }
+
     #[test]
     fn test_round_trip() {
         for entry in read_dir("tests").unwrap() {
@@ -1,28 +1,33 @@
+use super::error::{expected, tag, IResult};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_till, take_until, take_while1},
+    bytes::complete::{take_till, take_until, take_while1},
     character::complete::satisfy,
     combinator::recognize,
     sequence::{delimited, pair},
-    IResult,
 };
 
 pub fn string(input: &str) -> IResult<&str, &str> {
     delimited(tag("\""), take_until("\""), tag("\""))(input)
 }
 
+/// Characters that may follow the first one of a symbol
+pub fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '-'
+}
+
 fn simple(input: &str) -> IResult<&str, &str> {
     recognize(pair(
         satisfy(|c| c.is_alphabetic() || c == '_' || c == '.'),
-        take_till(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == '-')),
+        take_till(|c: char| !is_ident_char(c)),
     ))(input)
 }
 
 pub fn symbol(input: &str) -> IResult<&str, &str> {
-    alt((string, simple))(input)
+    expected("symbol", alt((string, simple)))(input)
 }
 
-fn is_pattern(c: char) -> bool {
+pub fn is_pattern(c: char) -> bool {
     c.is_alphanumeric() || "_.$/\\~=+[]*?-!<>^:".contains(c)
 }
 
@@ -31,7 +36,16 @@ fn simple_pattern(input: &str) -> IResult<&str, &str> {
 }
 
 pub fn pattern(input: &str) -> IResult<&str, &str> {
-    alt((string, simple_pattern))(input)
+    expected("pattern", alt((string, simple_pattern)))(input)
+}
+
+fn is_file_name(c: char) -> bool {
+    c.is_alphanumeric() || "_./\\$~+=:-".contains(c)
+}
+
+/// Parses a file name, as listed in `INPUT`, `GROUP` or `STARTUP`
+pub fn file_name(input: &str) -> IResult<&str, &str> {
+    expected("file name", alt((string, take_while1(is_file_name))))(input)
 }
 
 #[cfg(test)]
@@ -49,6 +63,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_file_name() {
+        assert_done!(file_name("crt0.o"), "crt0.o");
+        assert_done!(
+            file_name("/usr/lib/libc_nonshared.a"),
+            "/usr/lib/libc_nonshared.a"
+        );
+        assert_done!(file_name("-lgcc"), "-lgcc");
+        assert_done!(file_name("\"=/usr/local/lib\""), "=/usr/local/lib");
+        assert_done!(file_name("a.o,b.o"), "a.o");
+        assert_fail!(file_name("(a.o)"));
+    }
+
     #[test]
     fn test_pattern() {
         assert_done!(pattern("0"), "0");
@@ -0,0 +1,851 @@
+use crate::eval::{align_up, binary, Environment, EvalError};
+use crate::expressions::{BinaryOperator, Expression};
+use crate::matcher::{match_description, SortKey};
+use crate::memory::{MemoryCommand, Region};
+use crate::script::RootItem;
+use crate::sections::{
+    DataType, OutputSection, OutputSectionCommand, OutputSectionType, Overlay, SectionCommand,
+    SectionPattern,
+};
+use crate::statements::{AssignOperator, Statement};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Section of an input file, as offered to the layout.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InputSection {
+    /// Object file, or the member name for objects from an archive
+    pub file: String,
+    /// Archive the object was taken from
+    pub archive: Option<String>,
+    pub name: String,
+    pub size: u64,
+    pub align: u64,
+    /// Section flags from the object file, `sh_flags` for ELF
+    pub flags: u64,
+}
+
+impl InputSection {
+    pub fn new(file: &str, name: &str, size: u64) -> InputSection {
+        InputSection {
+            file: file.into(),
+            archive: None,
+            name: name.into(),
+            size,
+            align: 1,
+            flags: 0,
+        }
+    }
+
+    pub fn align(mut self, align: u64) -> InputSection {
+        self.align = align;
+        self
+    }
+}
+
+/// Output section with its final addresses.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PlacedSection {
+    pub name: String,
+    pub vma: u64,
+    pub lma: u64,
+    pub size: u64,
+    pub region: Option<String>,
+    pub lma_region: Option<String>,
+}
+
+/// Result of laying out the `SECTIONS` of a script.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Layout {
+    /// Output sections in the order they were placed
+    pub sections: Vec<PlacedSection>,
+    /// Final values of the symbols defined by the script
+    pub symbols: BTreeMap<String, u64>,
+    /// Input sections that were not matched by any input section description
+    pub orphans: Vec<InputSection>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LayoutError {
+    Eval(EvalError),
+    /// The location counter was moved backwards inside an output section
+    BackwardsLocation {
+        section: String,
+        from: u64,
+        to: u64,
+    },
+    RegionOverflow {
+        region: String,
+        section: String,
+        overflow: u64,
+    },
+    AssertionFailed(String),
+    /// `LINKER_VERSION` is as long as the version string of the linker
+    /// running the script, so the addresses after it are unknown
+    LinkerVersion {
+        section: String,
+    },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Eval(error) => error.fmt(f),
+            LayoutError::BackwardsLocation { section, from, to } => write!(
+                f,
+                "cannot move location counter backwards in `{}` (from {:#x} to {:#x})",
+                section, from, to
+            ),
+            LayoutError::RegionOverflow {
+                region,
+                section,
+                overflow,
+            } => write!(
+                f,
+                "section `{}` overflows region `{}` by {} bytes",
+                section, region, overflow
+            ),
+            LayoutError::AssertionFailed(text) => write!(f, "assertion failed: {}", text),
+            LayoutError::LinkerVersion { section } => write!(
+                f,
+                "size of LINKER_VERSION in `{}` depends on the linker",
+                section
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+impl From<EvalError> for LayoutError {
+    fn from(error: EvalError) -> Self {
+        LayoutError::Eval(error)
+    }
+}
+
+impl Layout {
+    /// Places `inputs` into the output sections of the script the way the
+    /// linker does, and computes the symbols the script defines.
+    ///
+    /// Root statements, `MEMORY` and `SECTIONS` are processed in order.
+    /// Input sections are assigned to the first description that matches
+    /// them. Inside an output section, plain numbers assigned to `.` or to a
+    /// symbol are offsets from the section start, as in ld.
+    pub fn compute(items: &[RootItem], inputs: &[InputSection]) -> Result<Layout, LayoutError> {
+        let mut state = State {
+            inputs,
+            placed: vec![false; inputs.len()],
+            dot: 0,
+            regions: Vec::new(),
+            layout: Layout::default(),
+        };
+        for item in items {
+            match item {
+                RootItem::Statement(statement) => state.statement(statement, None)?,
+                RootItem::Memory { commands, .. } => {
+                    for region in commands.iter().filter_map(MemoryCommand::region) {
+                        state.add_region(region)?;
+                    }
+                }
+                RootItem::Sections { list, .. } => {
+                    for command in list {
+                        state.section_command(command)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let State {
+            mut layout, placed, ..
+        } = state;
+        layout.orphans = inputs
+            .iter()
+            .zip(placed)
+            .filter(|(_, placed)| !placed)
+            .map(|(input, _)| input.clone())
+            .collect();
+        Ok(layout)
+    }
+
+    /// Looks up a placed output section by name
+    pub fn section(&self, name: &str) -> Option<&PlacedSection> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+}
+
+struct MemoryRegion {
+    name: String,
+    origin: u64,
+    length: u64,
+    /// Next free address
+    current: u64,
+}
+
+struct State<'a> {
+    inputs: &'a [InputSection],
+    placed: Vec<bool>,
+    /// Location counter, always an absolute address
+    dot: u64,
+    regions: Vec<MemoryRegion>,
+    layout: Layout,
+}
+
+impl Environment for State<'_> {
+    fn symbol(&self, name: &str) -> Option<u64> {
+        if name == "." {
+            Some(self.dot)
+        } else {
+            self.layout.symbols.get(name).copied()
+        }
+    }
+
+    fn region_origin(&self, region: &str) -> Option<u64> {
+        self.find_region(region).map(|region| region.origin)
+    }
+
+    fn region_length(&self, region: &str) -> Option<u64> {
+        self.find_region(region).map(|region| region.length)
+    }
+
+    fn section_address(&self, section: &str) -> Option<u64> {
+        self.layout.section(section).map(|section| section.vma)
+    }
+
+    fn section_load_address(&self, section: &str) -> Option<u64> {
+        self.layout.section(section).map(|section| section.lma)
+    }
+
+    fn section_size(&self, section: &str) -> Option<u64> {
+        self.layout.section(section).map(|section| section.size)
+    }
+}
+
+impl State<'_> {
+    fn eval(&self, expression: &Expression) -> Result<u64, LayoutError> {
+        Ok(expression.evaluate(self)?)
+    }
+
+    fn find_region(&self, name: &str) -> Option<&MemoryRegion> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    fn region_current(&self, name: &str) -> Result<u64, LayoutError> {
+        self.find_region(name)
+            .map(|region| region.current)
+            .ok_or_else(|| EvalError::UnknownRegion(name.into()).into())
+    }
+
+    fn add_region(&mut self, region: &Region) -> Result<(), LayoutError> {
+        let origin = self.eval(&region.origin)?;
+        let length = self.eval(&region.length)?;
+        self.regions.push(MemoryRegion {
+            name: region.name.clone(),
+            origin,
+            length,
+            current: origin,
+        });
+        Ok(())
+    }
+
+    /// Runs a statement, `section` is the name and start of the enclosing
+    /// output section if there is one.
+    fn statement(
+        &mut self,
+        statement: &Statement,
+        section: Option<(&str, u64)>,
+    ) -> Result<(), LayoutError> {
+        match statement {
+            Statement::Assign {
+                name,
+                operator,
+                expression,
+                ..
+            } => {
+                let value = self.eval(expression)?;
+                match compound_operator(operator) {
+                    Some(operator) => {
+                        let current = self
+                            .symbol(name)
+                            .ok_or_else(|| EvalError::UnknownSymbol(name.clone()))?;
+                        let value = binary(&operator, current, value)?;
+                        self.assign(name, value, false, section)
+                    }
+                    None => self.assign(name, value, is_number(expression), section),
+                }
+            }
+            Statement::Hidden {
+                name, expression, ..
+            } => {
+                let value = self.eval(expression)?;
+                self.assign(name, value, is_number(expression), section)
+            }
+            Statement::Provide {
+                name, expression, ..
+            }
+            | Statement::ProvideHidden {
+                name, expression, ..
+            } => {
+                if self.symbol(name).is_some() {
+                    return Ok(());
+                }
+                let value = self.eval(expression)?;
+                self.assign(name, value, is_number(expression), section)
+            }
+            Statement::Assert { expr, text, .. } => match self.eval(expr)? {
+                0 => Err(LayoutError::AssertionFailed(text.clone())),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Assigns `value` to a symbol or to `.`. A plain `number` inside an
+    /// output section is an offset from its start, anything else is an
+    /// address.
+    fn assign(
+        &mut self,
+        name: &str,
+        value: u64,
+        number: bool,
+        section: Option<(&str, u64)>,
+    ) -> Result<(), LayoutError> {
+        let value = match section {
+            Some((_, start)) if number => start.checked_add(value).ok_or(EvalError::Overflow)?,
+            _ => value,
+        };
+        if name != "." {
+            self.layout.symbols.insert(name.into(), value);
+            return Ok(());
+        }
+        if let Some((section, _)) = section {
+            if value < self.dot {
+                return Err(LayoutError::BackwardsLocation {
+                    section: section.into(),
+                    from: self.dot,
+                    to: value,
+                });
+            }
+        }
+        self.dot = value;
+        Ok(())
+    }
+
+    fn section_command(&mut self, command: &SectionCommand) -> Result<(), LayoutError> {
+        match command {
+            SectionCommand::Statement(statement) => self.statement(statement, None),
+            SectionCommand::Command(_) => Ok(()),
+            SectionCommand::OutputSection(section) => self.output_section(section),
+            SectionCommand::Overlay(overlay) => self.overlay(overlay),
+        }
+    }
+
+    fn output_section(&mut self, section: &OutputSection) -> Result<(), LayoutError> {
+        if section.name == "/DISCARD/" {
+            for command in &section.content {
+                for index in self.matches(command) {
+                    self.placed[index] = true;
+                }
+            }
+            return Ok(());
+        }
+
+        let subalign = match &section.subsection_align {
+            Some(align) => Some(self.eval(align)?),
+            None => None,
+        };
+        let mut align = self.content_alignment(&section.content, subalign);
+        if let Some(section_align) = &section.section_align {
+            align = align.max(self.eval(section_align)?);
+        }
+        let vma = match (&section.vma_address, &section.region) {
+            (Some(address), _) => self.eval(address)?,
+            (None, Some(region)) => align_up(self.region_current(region)?, align)?,
+            (None, None) => align_up(self.dot, align)?,
+        };
+        let lma = match (&section.lma_address, &section.lma_region) {
+            (Some(address), _) => self.eval(address)?,
+            (None, Some(region)) => align_up(self.region_current(region)?, align)?,
+            (None, None) => vma,
+        };
+
+        self.dot = vma;
+        self.content(&section.name, vma, &section.content, subalign)?;
+        let placed = PlacedSection {
+            name: section.name.clone(),
+            vma,
+            lma,
+            size: self.dot - vma,
+            region: section.region.clone(),
+            lma_region: section.lma_region.clone(),
+        };
+        self.place(placed, section.s_type == Some(OutputSectionType::NoLoad))
+    }
+
+    /// Places the sections of an overlay at the same address, with their
+    /// load addresses one after another.
+    fn overlay(&mut self, overlay: &Overlay) -> Result<(), LayoutError> {
+        let start = match (&overlay.start, &overlay.region) {
+            (Some(address), _) => self.eval(address)?,
+            (None, Some(region)) => self.region_current(region)?,
+            (None, None) => self.dot,
+        };
+        let mut lma = match (&overlay.lma_address, &overlay.lma_region) {
+            (Some(address), _) => self.eval(address)?,
+            (None, Some(region)) => self.region_current(region)?,
+            (None, None) => start,
+        };
+
+        let mut end = start;
+        for section in &overlay.sections {
+            self.dot = start;
+            self.content(&section.name, start, &section.content, None)?;
+            let size = self.dot - start;
+            let name: String = section
+                .name
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .collect();
+            let lma_end = lma.checked_add(size).ok_or(EvalError::Overflow)?;
+            let symbols = &mut self.layout.symbols;
+            symbols.insert(format!("__load_start_{}", name), lma);
+            symbols.insert(format!("__load_stop_{}", name), lma_end);
+
+            let placed = PlacedSection {
+                name: section.name.clone(),
+                vma: start,
+                lma,
+                size,
+                region: overlay.region.clone(),
+                lma_region: overlay.lma_region.clone(),
+            };
+            self.place(placed, false)?;
+            lma = lma_end;
+            end = end.max(start.checked_add(size).ok_or(EvalError::Overflow)?);
+        }
+        self.dot = end;
+        Ok(())
+    }
+
+    fn content(
+        &mut self,
+        section: &str,
+        start: u64,
+        content: &[OutputSectionCommand],
+        subalign: Option<u64>,
+    ) -> Result<(), LayoutError> {
+        for command in content {
+            match command {
+                OutputSectionCommand::Statement(statement) => {
+                    self.statement(statement, Some((section, start)))?
+                }
+                OutputSectionCommand::LinkerVersion { .. } => {
+                    return Err(LayoutError::LinkerVersion {
+                        section: section.into(),
+                    })
+                }
+                OutputSectionCommand::Fill { .. }
+                | OutputSectionCommand::FillPattern { .. }
+                | OutputSectionCommand::Include { .. }
+                | OutputSectionCommand::Constructors { .. }
+                | OutputSectionCommand::CreateObjectSymbols { .. } => {}
+                OutputSectionCommand::Data { d_type, .. } => {
+                    let size = match d_type {
+                        DataType::Byte => 1,
+                        DataType::Short => 2,
+                        DataType::Long => 4,
+                        DataType::Quad | DataType::SQuad => 8,
+                    };
+                    self.dot = self.dot.checked_add(size).ok_or(EvalError::Overflow)?;
+                }
+                OutputSectionCommand::Asciz { string, .. } => {
+                    let size = string.len() as u64 + 1;
+                    self.dot = self.dot.checked_add(size).ok_or(EvalError::Overflow)?;
+                }
+                OutputSectionCommand::InputSection { .. }
+                | OutputSectionCommand::KeepInputSection { .. } => {
+                    for index in self.matches(command) {
+                        let input = &self.inputs[index];
+                        let address = align_up(self.dot, subalign.unwrap_or(input.align))?;
+                        self.dot = address.checked_add(input.size).ok_or(EvalError::Overflow)?;
+                        self.placed[index] = true;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Largest alignment of the input sections that `content` would place
+    fn content_alignment(&self, content: &[OutputSectionCommand], subalign: Option<u64>) -> u64 {
+        content
+            .iter()
+            .flat_map(|command| self.matches(command))
+            .map(|index| subalign.unwrap_or(self.inputs[index].align))
+            .fold(1, u64::max)
+    }
+
+    /// Indices of the unplaced input sections matched by a description, in
+    /// the order they are placed. The matches of a sorted pattern are sorted
+    /// among themselves, the others keep their input order.
+    fn matches(&self, description: &OutputSectionCommand) -> Vec<usize> {
+        let sections = match description {
+            OutputSectionCommand::InputSection { sections, .. }
+            | OutputSectionCommand::KeepInputSection { sections, .. } => sections,
+            _ => return Vec::new(),
+        };
+        let matched: Vec<(usize, &SectionPattern)> = (0..self.inputs.len())
+            .filter(|&index| !self.placed[index])
+            .filter_map(|index| {
+                match_description(description, &self.inputs[index]).map(|pattern| (index, pattern))
+            })
+            .collect();
+        let mut matches: Vec<usize> = matched.iter().map(|&(index, _)| index).collect();
+
+        let inputs = self.inputs;
+        for pattern in sections {
+            let keys = pattern.sort_keys();
+            if keys.is_empty() {
+                continue;
+            }
+            let positions: Vec<usize> = (0..matched.len())
+                .filter(|&position| std::ptr::eq(matched[position].1, pattern))
+                .collect();
+            let mut sorted: Vec<usize> = positions
+                .iter()
+                .map(|&position| matches[position])
+                .collect();
+            sorted.sort_by(|&a, &b| compare_sections(&keys, &inputs[a], &inputs[b]));
+            for (position, index) in positions.into_iter().zip(sorted) {
+                matches[position] = index;
+            }
+        }
+        matches
+    }
+
+    /// Assigns the final addresses of a section and moves the location
+    /// counter and the regions past it.
+    fn place(&mut self, placed: PlacedSection, noload: bool) -> Result<(), LayoutError> {
+        let vma_end = placed
+            .vma
+            .checked_add(placed.size)
+            .ok_or(EvalError::Overflow)?;
+        let lma_end = placed
+            .lma
+            .checked_add(placed.size)
+            .ok_or(EvalError::Overflow)?;
+        if let Some(region) = &placed.region {
+            self.advance_region(region, &placed.name, vma_end)?;
+        }
+        if let (Some(region), false) = (&placed.lma_region, noload) {
+            self.advance_region(region, &placed.name, lma_end)?;
+        }
+        self.dot = vma_end;
+        self.layout.sections.push(placed);
+        Ok(())
+    }
+
+    fn advance_region(&mut self, name: &str, section: &str, end: u64) -> Result<(), LayoutError> {
+        let region = self
+            .regions
+            .iter_mut()
+            .find(|region| region.name == name)
+            .ok_or_else(|| EvalError::UnknownRegion(name.into()))?;
+        let limit = region.origin.saturating_add(region.length);
+        if end > limit {
+            return Err(LayoutError::RegionOverflow {
+                region: name.into(),
+                section: section.into(),
+                overflow: end - limit,
+            });
+        }
+        region.current = region.current.max(end);
+        Ok(())
+    }
+}
+
+fn compound_operator(operator: &AssignOperator) -> Option<BinaryOperator> {
+    match operator {
+        AssignOperator::Equals => None,
+        AssignOperator::Plus => Some(BinaryOperator::Plus),
+        AssignOperator::Minus => Some(BinaryOperator::Minus),
+        AssignOperator::Multiply => Some(BinaryOperator::Multiply),
+        AssignOperator::Divide => Some(BinaryOperator::Divide),
+        AssignOperator::ShiftLeft => Some(BinaryOperator::ShiftLeft),
+        AssignOperator::ShiftRight => Some(BinaryOperator::ShiftRight),
+        AssignOperator::And => Some(BinaryOperator::BitwiseAnd),
+        AssignOperator::Or => Some(BinaryOperator::BitwiseOr),
+    }
+}
+
+/// Whether `expression` is a plain number rather than an address, like a
+/// value without a section in ld's `etree_value_type`. `.`, symbols and the
+/// builtins returning addresses make an address.
+fn is_number(expression: &Expression) -> bool {
+    match expression {
+        Expression::Number(_) => true,
+        Expression::Ident(_) => false,
+        Expression::Call {
+            function,
+            arguments,
+            ..
+        } => match function.as_str() {
+            "LENGTH" | "SIZEOF" | "ALIGNOF" | "CONSTANT" | "DEFINED" | "SIZEOF_HEADERS" => true,
+            "MAX" | "MIN" | "LOG2CEIL" => arguments.iter().all(is_number),
+            "ALIGN" => arguments.len() == 2 && is_number(&arguments[0]),
+            _ => false,
+        },
+        Expression::UnaryOp { right, .. } => is_number(right),
+        Expression::BinaryOp { left, right, .. } => is_number(left) && is_number(right),
+        Expression::TernaryOp { left, right, .. } => is_number(left) && is_number(right),
+    }
+}
+
+/// Order of two input sections by the sort keys of the pattern they matched
+fn compare_sections(keys: &[(SortKey, bool)], a: &InputSection, b: &InputSection) -> Ordering {
+    keys.iter()
+        .map(|&(key, reversed)| {
+            let ordering = match key {
+                SortKey::Name => a.name.cmp(&b.name),
+                SortKey::Alignment => b.align.cmp(&a.align),
+                SortKey::InitPriority => init_priority(&a.name).cmp(&init_priority(&b.name)),
+            };
+            if reversed {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+        .fold(Ordering::Equal, Ordering::then)
+}
+
+/// Priority from a `.init_array.NNNNN` style name, sections without one go
+/// last.
+fn init_priority(name: &str) -> u64 {
+    name.rsplit('.')
+        .next()
+        .and_then(|suffix| suffix.parse().ok())
+        .unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout::*;
+
+    const SCRIPT: &str = "
+        MEMORY {
+            FLASH (rx) : ORIGIN = 0x08000000, LENGTH = 64K
+            RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 8K
+        }
+        _stack_size = 0x400;
+        SECTIONS {
+            .vectors : { KEEP(*(.vectors)) } > FLASH
+            .text : ALIGN(16) {
+                *(.text .text.*)
+                *(.rodata*)
+                . = ALIGN(4);
+                _etext = .;
+            } > FLASH
+            .data : {
+                _sdata = .;
+                *(.data*)
+                _edata = .;
+            } > RAM AT> FLASH
+            _sidata = LOADADDR(.data);
+            .bss (NOLOAD) : SUBALIGN(8) {
+                _sbss = .;
+                *(.bss*) *(COMMON)
+                _ebss = .;
+            } > RAM
+            PROVIDE(_estack = ORIGIN(RAM) + LENGTH(RAM));
+            PROVIDE(_sdata = 0);
+            /DISCARD/ : { *(.comment) }
+        }
+    ";
+
+    fn inputs() -> Vec<InputSection> {
+        vec![
+            InputSection::new("main.o", ".text", 0x102).align(2),
+            InputSection::new("main.o", ".rodata.str", 0x11),
+            InputSection::new("main.o", ".data", 0x10).align(4),
+            InputSection::new("main.o", ".bss", 0x20).align(4),
+            InputSection::new("main.o", ".comment", 0x30),
+            InputSection::new("startup.o", ".vectors", 0xc0).align(4),
+            InputSection::new("startup.o", ".text.reset", 0x20).align(4),
+            InputSection::new("startup.o", "COMMON", 0x4),
+            InputSection::new("startup.o", ".ARM.attributes", 0x2c),
+        ]
+    }
+
+    #[test]
+    fn test_layout() {
+        let script = crate::parse(SCRIPT).unwrap();
+        let layout = Layout::compute(&script, &inputs()).unwrap();
+
+        let sections: Vec<_> = layout
+            .sections
+            .iter()
+            .map(|s| (s.name.as_str(), s.vma, s.lma, s.size))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![
+                (".vectors", 0x0800_0000, 0x0800_0000, 0xc0),
+                (".text", 0x0800_00c0, 0x0800_00c0, 0x138),
+                (".data", 0x2000_0000, 0x0800_01f8, 0x10),
+                (".bss", 0x2000_0010, 0x2000_0010, 0x24),
+            ]
+        );
+
+        let symbol = |name: &str| layout.symbols.get(name).copied();
+        assert_eq!(symbol("_stack_size"), Some(0x400));
+        assert_eq!(symbol("_etext"), Some(0x0800_01f8));
+        assert_eq!(symbol("_sdata"), Some(0x2000_0000));
+        assert_eq!(symbol("_edata"), Some(0x2000_0010));
+        assert_eq!(symbol("_sidata"), Some(0x0800_01f8));
+        assert_eq!(symbol("_sbss"), Some(0x2000_0010));
+        assert_eq!(symbol("_ebss"), Some(0x2000_0034));
+        assert_eq!(symbol("_estack"), Some(0x2000_2000));
+
+        assert_eq!(
+            layout.orphans,
+            vec![InputSection::new("startup.o", ".ARM.attributes", 0x2c)]
+        );
+    }
+
+    #[test]
+    fn test_overlay() {
+        let script = crate::parse(
+            "SECTIONS {
+                . = 0x1000;
+                OVERLAY 0x4000 : AT(0x8000) {
+                    .ov1 { a.o(.text) }
+                    .ov2 { b.o(.text) BYTE(1) }
+                }
+                _end = .;
+            }",
+        )
+        .unwrap();
+        let inputs = vec![
+            InputSection::new("a.o", ".text", 0x100),
+            InputSection::new("b.o", ".text", 0x200),
+        ];
+        let layout = Layout::compute(&script, &inputs).unwrap();
+        assert_eq!(layout.section(".ov1").unwrap().vma, 0x4000);
+        assert_eq!(layout.section(".ov2").unwrap().vma, 0x4000);
+        assert_eq!(layout.section(".ov2").unwrap().lma, 0x8100);
+        assert_eq!(layout.symbols["__load_start_ov2"], 0x8100);
+        assert_eq!(layout.symbols["__load_stop_ov2"], 0x8301);
+        assert_eq!(layout.symbols["_end"], 0x4201);
+    }
+
+    #[test]
+    fn test_assignments() {
+        let script = crate::parse(
+            "SECTIONS {
+                . = 0x80;
+                _root = 0x40;
+                .x 0x100 : {
+                    _start = .;
+                    . = 0x200;
+                    _dot = .;
+                    _offset = 0x10;
+                    _address = ABSOLUTE(0x380);
+                    _next = . + 0x20;
+                    . = _root + 0x300;
+                    _moved = .;
+                    . = ALIGN(0x100);
+                    _end = .;
+                }
+            }",
+        )
+        .unwrap();
+        let layout = Layout::compute(&script, &[]).unwrap();
+        assert_eq!(layout.symbols["_root"], 0x40);
+        assert_eq!(layout.symbols["_start"], 0x100);
+        assert_eq!(layout.symbols["_dot"], 0x300);
+        assert_eq!(layout.symbols["_offset"], 0x110);
+        assert_eq!(layout.symbols["_address"], 0x380);
+        assert_eq!(layout.symbols["_next"], 0x320);
+        assert_eq!(layout.symbols["_moved"], 0x340);
+        assert_eq!(layout.symbols["_end"], 0x400);
+        assert_eq!(layout.section(".x").unwrap().size, 0x300);
+    }
+
+    #[test]
+    fn test_sorting() {
+        // Placing the smaller alignment first costs padding
+        let size = |pattern: &str| {
+            let script = format!("SECTIONS {{ .data : {{ *({}) }} }}", pattern);
+            let inputs = vec![
+                InputSection::new("x.o", ".data.x", 1),
+                InputSection::new("y.o", ".data.y", 4).align(4),
+            ];
+            let layout = Layout::compute(&crate::parse(&script).unwrap(), &inputs).unwrap();
+            layout.section(".data").unwrap().size
+        };
+        assert_eq!(size(".data.*"), 8);
+        assert_eq!(size("SORT_BY_ALIGNMENT(.data.*)"), 5);
+        assert_eq!(size("REVERSE(SORT_BY_ALIGNMENT(.data.*))"), 8);
+        assert_eq!(size("REVERSE(.data.*)"), 5);
+        assert_eq!(size("SORT_BY_NAME(SORT_BY_ALIGNMENT(.data.*))"), 8);
+        assert_eq!(size("SORT_BY_ALIGNMENT(REVERSE(.data.*))"), 8);
+        assert_eq!(size("SORT_BY_NAME(REVERSE(.data.*))"), 5);
+
+        // Each section is sorted by the pattern it matched
+        let size = |patterns: &str| {
+            let script = format!("SECTIONS {{ .data : {{ *({}) }} }}", patterns);
+            let inputs = vec![
+                InputSection::new("x.o", ".b.x", 1),
+                InputSection::new("y.o", ".b.y", 4).align(4),
+            ];
+            let layout = Layout::compute(&crate::parse(&script).unwrap(), &inputs).unwrap();
+            layout.section(".data").unwrap().size
+        };
+        assert_eq!(size("SORT_BY_NAME(.a.*) SORT_BY_ALIGNMENT(.b.*)"), 5);
+        assert_eq!(size("SORT_BY_ALIGNMENT(.a.*) SORT_BY_NAME(.b.*)"), 8);
+        assert_eq!(size("SORT_BY_ALIGNMENT(.a.*) .b.*"), 8);
+    }
+
+    #[test]
+    fn test_errors() {
+        let layout = |script: &str| {
+            let inputs = vec![InputSection::new("a.o", ".text", 0x100)];
+            Layout::compute(&crate::parse(script).unwrap(), &inputs)
+        };
+        assert_eq!(
+            layout("MEMORY { ROM : ORIGIN = 0, LENGTH = 0x80 } SECTIONS { .text : { *(.text) } > ROM }"),
+            Err(LayoutError::RegionOverflow {
+                region: "ROM".into(),
+                section: ".text".into(),
+                overflow: 0x80,
+            })
+        );
+        assert_eq!(
+            layout("SECTIONS { .text 0x10 : { *(.text) . = 0x20; } }"),
+            Err(LayoutError::BackwardsLocation {
+                section: ".text".into(),
+                from: 0x110,
+                to: 0x30,
+            })
+        );
+        assert_eq!(
+            layout("SECTIONS { .text : { *(.text) } > ROM }"),
+            Err(LayoutError::Eval(EvalError::UnknownRegion("ROM".into())))
+        );
+        assert_eq!(
+            layout("SECTIONS { .text : { *(.text) } ASSERT(SIZEOF(.text) < 0x80, \"too big\") }"),
+            Err(LayoutError::AssertionFailed("too big".into()))
+        );
+        assert_eq!(
+            layout("SECTIONS { OVERLAY 0 : AT(0xffffffffffffff80) { .ov { *(.text) } } }"),
+            Err(LayoutError::Eval(EvalError::Overflow))
+        );
+        assert_eq!(
+            layout("SECTIONS { .rodata : { LINKER_VERSION } .text : { *(.text) } }"),
+            Err(LayoutError::LinkerVersion {
+                section: ".rodata".into(),
+            })
+        );
+    }
+}
@@ -17,6 +17,23 @@
 //! }
 //! ```
 //!
+//! Parse errors carry the position of the failure, what was expected there
+//! and the enclosing constructs:
+//!
+//! ```
+//! extern crate ldscript_parser as lds;
+//!
+//! let script = "SECTIONS {\n  .data : { *(.data) \n}";
+//! let error = lds::parse(script).unwrap_err();
+//! assert_eq!(error.line, 3);
+//! println!("error: {}\n{}", error, error.snippet(script));
+//! ```
+//!
+//! # Features
+//!
+//! - `elf`: read the input sections of ELF relocatable objects and archives
+//!   with `read_input_sections`, to feed `Layout::compute`.
+//!
 //! # References
 //!
 //! - [GNU binutils documentation](https://sourceware.org/binutils/docs/ld/Scripts.html#Scripts)
@@ -30,36 +47,98 @@ mod utils;
 mod whitespace;
 pub mod builder;
 mod commands;
+mod diagnostic;
+mod document;
+mod error;
+mod eval;
 mod expressions;
 pub mod generator;
 mod idents;
+mod layout;
+mod lint;
+mod loader;
+mod matcher;
 mod memory;
 mod numbers;
+#[cfg(feature = "elf")]
+mod objects;
+mod phdrs;
 mod script;
 mod sections;
+mod span;
 mod statements;
+mod validate;
+mod version;
 
 pub use commands::Command;
+pub use commands::InputFile;
+pub use commands::InsertOrder;
+pub use commands::NullaryCommand;
+pub use diagnostic::Diagnostic;
+pub use diagnostic::Severity;
+pub use document::Document;
+pub use error::ParseError;
+pub use eval::Environment;
+pub use eval::EvalError;
 pub use expressions::BinaryOperator;
 pub use expressions::Expression;
 pub use expressions::UnaryOperator;
+pub use layout::InputSection;
+pub use layout::Layout;
+pub use layout::LayoutError;
+pub use layout::PlacedSection;
+pub use lint::{Linter, Rule, UnknownRule};
+pub use loader::{FileLoader, FsLoader, InputPath, LoadError, LoadedFile, LoadedScript, Loader};
+pub use matcher::{find_output_section, match_description, SectionMatch, SortKey};
+pub use memory::MemoryCommand;
 pub use memory::Region;
+pub use memory::RegionAttributes;
+pub use memory::RegionFlags;
+#[cfg(feature = "elf")]
+pub use objects::{parse_input_sections, read_input_sections, ObjectError};
+pub use phdrs::ProgramHeader;
+pub use phdrs::ProgramHeaderType;
 pub use script::RootItem;
 pub use sections::DataType;
+pub use sections::InputSectionFlags;
 pub use sections::OutputSection;
 pub use sections::OutputSectionCommand;
 pub use sections::OutputSectionConstraint;
 pub use sections::OutputSectionType;
+pub use sections::Overlay;
+pub use sections::OverlaySection;
 pub use sections::SectionCommand;
 pub use sections::SectionPattern;
+pub use span::SourceSpan;
+pub use span::Span;
+pub use span::Spanned;
 pub use statements::AssignOperator;
 pub use statements::Statement;
+pub use validate::validate;
+pub use version::VersionEntry;
+pub use version::VersionNode;
 
 /// Parses the string that contains a linker script
-pub fn parse(ldscript: &str) -> Result<Vec<RootItem>, String> {
-    match script::parse(ldscript) {
-        Ok((_, result)) => Ok(result),
-        //TODO: add error handling
-        Err(e) => Err(format!("Parsing failed, error: {:?}", e)),
+pub fn parse(ldscript: &str) -> Result<Vec<RootItem>, ParseError> {
+    parse_with(script::parse, ldscript)
+}
+
+/// Parses the string that contains a standalone version script, as passed to
+/// `--version-script`
+pub fn parse_version_script(script: &str) -> Result<Vec<VersionNode>, ParseError> {
+    parse_with(version::parse, script)
+}
+
+/// Runs a parser over a whole source and resolves the spans of the result
+fn parse_with<O: span::VisitSpans>(
+    parser: fn(&str) -> error::IResult<&str, Vec<O>>,
+    source: &str,
+) -> Result<Vec<O>, ParseError> {
+    match parser(source) {
+        Ok((_, mut result)) => {
+            span::resolve(&mut result, source);
+            Ok(result)
+        }
+        Err(e) => Err(ParseError::new(source, e)),
     }
 }
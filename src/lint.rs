@@ -0,0 +1,714 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error::ParseError;
+use crate::expressions::Expression;
+use crate::matcher::{wildcard, SortKey};
+use crate::script::RootItem;
+use crate::sections::{OutputSectionCommand, OutputSectionType, SectionCommand, SectionPattern};
+use crate::span::{Span, Spanned};
+use crate::validate::VisitExpressions;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A check over a parsed script.
+pub trait Rule {
+    /// Stable name of the rule, used as the code of its diagnostics and to
+    /// configure or suppress it
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Rules that only make sense for some builds are off unless enabled
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn check(&self, items: &[RootItem]) -> Vec<Diagnostic>;
+
+    /// Diagnostic of this rule with its default severity
+    fn diagnostic(&self, message: String, span: Option<Span>) -> Diagnostic {
+        Diagnostic {
+            severity: self.default_severity(),
+            code: self.name(),
+            message,
+            span,
+        }
+    }
+}
+
+/// Registry of lint rules with their configuration.
+///
+/// Diagnostics can be suppressed from the script with a comment, for the
+/// line the comment ends on and the line after it:
+///
+/// ```text
+/// /* ldlint: allow(missing-keep) */
+/// ```
+///
+/// or for the whole script with `allow-file(...)`. `all` suppresses every
+/// rule.
+///
+/// Configuring a rule the linter doesn't have is an error.
+///
+/// ```
+/// extern crate ldscript_parser as lds;
+///
+/// let linter = lds::Linter::new()
+///     .disable("deprecated-section-type")
+///     .and_then(|linter| linter.severity("missing-keep", lds::Severity::Error))
+///     .unwrap();
+/// let diagnostics = linter
+///     .check_source("SECTIONS { .vectors : { *(.isr_vector) } }")
+///     .unwrap();
+/// assert_eq!(diagnostics[0].code, "missing-keep");
+/// assert_eq!(diagnostics[0].severity, lds::Severity::Error);
+/// assert!(lds::Linter::new().disable("missing-kept").is_err());
+/// ```
+pub struct Linter {
+    rules: Vec<Box<dyn Rule>>,
+    /// Configured severity of a rule, `None` if it is disabled
+    levels: HashMap<String, Option<Severity>>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+impl Linter {
+    /// Linter with the built-in rules
+    pub fn new() -> Linter {
+        Linter::empty()
+            .rule(MissingKeep)
+            .rule(InitArrayOrder)
+            .rule(AlignPowerOfTwo)
+            .rule(MissingExidxDiscard)
+            .rule(DeprecatedSectionType)
+    }
+
+    /// Linter without any rule
+    pub fn empty() -> Linter {
+        Linter {
+            rules: Vec::new(),
+            levels: HashMap::new(),
+        }
+    }
+
+    pub fn rule<R: Rule + 'static>(mut self, rule: R) -> Linter {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Enables a rule with its default severity
+    pub fn enable(mut self, rule: &str) -> Result<Linter, UnknownRule> {
+        let severity = self.find_rule(rule)?.default_severity();
+        self.levels.insert(rule.into(), Some(severity));
+        Ok(self)
+    }
+
+    pub fn disable(mut self, rule: &str) -> Result<Linter, UnknownRule> {
+        self.find_rule(rule)?;
+        self.levels.insert(rule.into(), None);
+        Ok(self)
+    }
+
+    /// Enables a rule and reports its diagnostics with `severity`
+    pub fn severity(mut self, rule: &str, severity: Severity) -> Result<Linter, UnknownRule> {
+        self.find_rule(rule)?;
+        self.levels.insert(rule.into(), Some(severity));
+        Ok(self)
+    }
+
+    fn find_rule(&self, name: &str) -> Result<&dyn Rule, UnknownRule> {
+        self.rules()
+            .find(|rule| rule.name() == name)
+            .ok_or_else(|| UnknownRule(name.into()))
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.rules.iter().map(|rule| rule.as_ref())
+    }
+
+    /// Severity the diagnostics of a rule are reported with, `None` if the
+    /// rule is disabled
+    pub fn level(&self, rule: &dyn Rule) -> Option<Severity> {
+        match self.levels.get(rule.name()) {
+            Some(level) => *level,
+            None => rule.enabled_by_default().then(|| rule.default_severity()),
+        }
+    }
+
+    /// Runs the enabled rules, ordering diagnostics by their position
+    pub fn check(&self, items: &[RootItem]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for rule in self.rules() {
+            let Some(severity) = self.level(rule) else {
+                continue;
+            };
+            diagnostics.extend(rule.check(items).into_iter().map(|diagnostic| Diagnostic {
+                severity,
+                ..diagnostic
+            }));
+        }
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.map_or(usize::MAX, |span| span.start));
+        diagnostics
+    }
+
+    /// Parses a script and runs the enabled rules, honoring the suppression
+    /// comments of the script
+    pub fn check_source(&self, source: &str) -> Result<Vec<Diagnostic>, ParseError> {
+        let items = crate::parse(source)?;
+        let suppressions = suppressions(source);
+        let mut diagnostics = self.check(&items);
+        diagnostics.retain(|diagnostic| {
+            !suppressions.iter().any(|suppression| {
+                suppression.covers(diagnostic.span.map(|span| span.line))
+                    && suppression
+                        .rules
+                        .iter()
+                        .any(|rule| rule == "all" || rule == diagnostic.code)
+            })
+        });
+        Ok(diagnostics)
+    }
+}
+
+/// Error returned when configuring a rule the linter doesn't have.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UnknownRule(pub String);
+
+impl fmt::Display for UnknownRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown lint rule `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownRule {}
+
+struct Suppression {
+    rules: Vec<String>,
+    /// Line the comment ends on, `None` for the whole file
+    line: Option<usize>,
+}
+
+impl Suppression {
+    fn covers(&self, line: Option<usize>) -> bool {
+        match (self.line, line) {
+            (None, _) => true,
+            (Some(comment), Some(line)) => line == comment || line == comment + 1,
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Finds the `ldlint:` comments of a script, skipping string literals.
+fn suppressions(source: &str) -> Vec<Suppression> {
+    let mut suppressions = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find(['"', '/']) {
+        let tail = &rest[start..];
+        if let Some(string) = tail.strip_prefix('"') {
+            rest = string.find('"').map_or("", |end| &string[end + 1..]);
+        } else if let Some(comment) = tail.strip_prefix("/*") {
+            let end = comment.find("*/").unwrap_or(comment.len());
+            let offset = source.len() - comment.len() + end;
+            let line = source[..offset].matches('\n').count() + 1;
+            suppressions.extend(directive(&comment[..end], line));
+            rest = comment.get(end + 2..).unwrap_or("");
+        } else {
+            rest = &tail[1..];
+        }
+    }
+    suppressions
+}
+
+fn directive(comment: &str, line: usize) -> Option<Suppression> {
+    let directive = comment.trim().strip_prefix("ldlint:")?.trim();
+    let (line, rules) = match directive.strip_prefix("allow-file(") {
+        Some(rules) => (None, rules),
+        None => (Some(line), directive.strip_prefix("allow(")?),
+    };
+    let rules = rules.strip_suffix(')')?;
+    Some(Suppression {
+        rules: rules
+            .split(',')
+            .map(|rule| rule.trim().to_string())
+            .collect(),
+        line,
+    })
+}
+
+/// Name and output section commands of every output section and overlay
+/// section
+fn section_contents(items: &[RootItem]) -> Vec<(&str, &[OutputSectionCommand])> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            RootItem::Sections { list, .. } => Some(list),
+            _ => None,
+        })
+        .flatten()
+        .flat_map(|command| match command {
+            SectionCommand::OutputSection(section) => {
+                vec![(section.name.as_str(), section.content.as_slice())]
+            }
+            SectionCommand::Overlay(overlay) => overlay
+                .sections
+                .iter()
+                .map(|section| (section.name.as_str(), section.content.as_slice()))
+                .collect(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Section patterns of the input section descriptions in a script, with
+/// the description and whether it is wrapped in `KEEP`
+fn section_patterns(items: &[RootItem]) -> Vec<(&OutputSectionCommand, &SectionPattern, bool)> {
+    let mut patterns = Vec::new();
+    for (_, content) in section_contents(items) {
+        for command in content {
+            let (sections, keep) = match command {
+                OutputSectionCommand::InputSection { sections, .. } => (sections, false),
+                OutputSectionCommand::KeepInputSection { sections, .. } => (sections, true),
+                _ => continue,
+            };
+            patterns.extend(sections.iter().map(|pattern| (command, pattern, keep)));
+        }
+    }
+    patterns
+}
+
+/// Interrupt vector tables are only referenced by the hardware, so
+/// `--gc-sections` drops them unless they are kept.
+struct MissingKeep;
+
+/// Names that vector table sections start with
+const VECTOR_SECTIONS: &[&str] = &[".isr_vector", ".vectors", ".vector_table"];
+
+impl Rule for MissingKeep {
+    fn name(&self) -> &'static str {
+        "missing-keep"
+    }
+
+    fn description(&self) -> &'static str {
+        "interrupt vector sections should be wrapped in KEEP"
+    }
+
+    fn check(&self, items: &[RootItem]) -> Vec<Diagnostic> {
+        section_patterns(items)
+            .into_iter()
+            .filter(|(_, pattern, keep)| {
+                let name = pattern.wildcard();
+                !keep
+                    && VECTOR_SECTIONS
+                        .iter()
+                        .any(|prefix| name.starts_with(prefix))
+            })
+            .map(|(command, pattern, _)| {
+                self.diagnostic(
+                    format!(
+                        "`{}` looks like an interrupt vector table and is not wrapped in KEEP",
+                        pattern.wildcard()
+                    ),
+                    command.span(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Constructors with a priority must run in priority order, which only
+/// `SORT_BY_INIT_PRIORITY` guarantees.
+struct InitArrayOrder;
+
+impl Rule for InitArrayOrder {
+    fn name(&self) -> &'static str {
+        "init-array-order"
+    }
+
+    fn description(&self) -> &'static str {
+        "prioritized .init_array and .fini_array sections should use SORT_BY_INIT_PRIORITY"
+    }
+
+    fn check(&self, items: &[RootItem]) -> Vec<Diagnostic> {
+        section_patterns(items)
+            .into_iter()
+            .filter(|(_, pattern, _)| {
+                let name = pattern.wildcard();
+                (name.starts_with(".init_array.") || name.starts_with(".fini_array."))
+                    && !pattern
+                        .sort_keys()
+                        .iter()
+                        .any(|(key, _)| *key == SortKey::InitPriority)
+            })
+            .map(|(command, pattern, _)| {
+                self.diagnostic(
+                    format!(
+                        "`{}` should be sorted with SORT_BY_INIT_PRIORITY",
+                        pattern.wildcard()
+                    ),
+                    command.span(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Alignments are powers of two, anything else is almost always a typo.
+struct AlignPowerOfTwo;
+
+impl AlignPowerOfTwo {
+    /// Checks `alignment`, reported at `parent` when it is a plain number
+    /// without a span of its own.
+    fn check_alignment(
+        &self,
+        alignment: &Expression,
+        parent: &impl Spanned,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if let Some(value) = alignment.constant_value() {
+            if !value.is_power_of_two() {
+                diagnostics.push(self.diagnostic(
+                    format!("alignment {} is not a power of two", value),
+                    alignment.span().or(parent.span()),
+                ));
+            }
+        }
+    }
+}
+
+impl Rule for AlignPowerOfTwo {
+    fn name(&self) -> &'static str {
+        "align-power-of-two"
+    }
+
+    fn description(&self) -> &'static str {
+        "alignments should be powers of two"
+    }
+
+    fn check(&self, items: &[RootItem]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        items.visit_expressions(&mut |expression| {
+            if let Expression::Call {
+                function,
+                arguments,
+                ..
+            } = expression
+            {
+                if function == "ALIGN" {
+                    if let Some(alignment) = arguments.last() {
+                        self.check_alignment(alignment, expression, &mut diagnostics);
+                    }
+                }
+            }
+        });
+        for item in items {
+            let RootItem::Sections { list, .. } = item else {
+                continue;
+            };
+            for command in list {
+                if let SectionCommand::OutputSection(section) = command {
+                    for alignment in [&section.section_align, &section.subsection_align]
+                        .into_iter()
+                        .flatten()
+                    {
+                        self.check_alignment(alignment, section, &mut diagnostics);
+                    }
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Without unwinding, `.ARM.exidx` tables are dead weight and should be
+/// discarded. Off by default as builds with unwinding need them.
+struct MissingExidxDiscard;
+
+impl Rule for MissingExidxDiscard {
+    fn name(&self) -> &'static str {
+        "missing-exidx-discard"
+    }
+
+    fn description(&self) -> &'static str {
+        ".ARM.exidx sections should be discarded in builds without unwinding"
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn check(&self, items: &[RootItem]) -> Vec<Diagnostic> {
+        let Some(sections) = items
+            .iter()
+            .find(|item| matches!(item, RootItem::Sections { .. }))
+        else {
+            return Vec::new();
+        };
+        // The first description taking `.ARM.exidx` from a file wins, only
+        // discarding them from every file settles it
+        for (name, content) in section_contents(items) {
+            for description in content {
+                match exidx_files(description) {
+                    None => continue,
+                    Some(_) if name != "/DISCARD/" => {
+                        return vec![self.diagnostic(
+                            format!(
+                                "`.ARM.exidx` is placed in `{}` instead of being discarded",
+                                name
+                            ),
+                            description.span(),
+                        )]
+                    }
+                    Some(true) => return Vec::new(),
+                    Some(false) => {}
+                }
+            }
+        }
+        vec![self.diagnostic("`.ARM.exidx` is not discarded".into(), sections.span())]
+    }
+}
+
+/// Whether an input section description takes `.ARM.exidx` sections, and if
+/// so whether it takes them from every file
+fn exidx_files(description: &OutputSectionCommand) -> Option<bool> {
+    let (flags, file, sections) = match description {
+        OutputSectionCommand::InputSection {
+            flags,
+            file,
+            sections,
+            ..
+        }
+        | OutputSectionCommand::KeepInputSection {
+            flags,
+            file,
+            sections,
+            ..
+        } => (flags, file, sections),
+        _ => return None,
+    };
+    let every_file = flags.is_none() && file.wildcard() == "*" && !excludes_files(file);
+    if sections.is_empty() {
+        return Some(every_file);
+    }
+    sections
+        .iter()
+        .filter(|pattern| {
+            [".ARM.exidx", ".ARM.exidx.text"]
+                .iter()
+                .any(|name| wildcard(pattern.wildcard(), name))
+        })
+        .map(|pattern| every_file && !excludes_files(pattern))
+        .reduce(|a, b| a || b)
+}
+
+fn excludes_files(pattern: &SectionPattern) -> bool {
+    match pattern {
+        SectionPattern::Simple(_) => false,
+        SectionPattern::ExcludeFile { .. } => true,
+        SectionPattern::SortByName(pattern)
+        | SectionPattern::SortByAlignment(pattern)
+        | SectionPattern::SortByInitPriority(pattern)
+        | SectionPattern::SortNone(pattern)
+        | SectionPattern::Reverse(pattern) => excludes_files(pattern),
+    }
+}
+
+/// `DSECT` and `COPY` are kept by GNU ld for compatibility only and behave
+/// like `INFO`.
+struct DeprecatedSectionType;
+
+impl Rule for DeprecatedSectionType {
+    fn name(&self) -> &'static str {
+        "deprecated-section-type"
+    }
+
+    fn description(&self) -> &'static str {
+        "the DSECT and COPY section types are deprecated"
+    }
+
+    fn check(&self, items: &[RootItem]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for item in items {
+            let RootItem::Sections { list, .. } = item else {
+                continue;
+            };
+            for command in list {
+                let SectionCommand::OutputSection(section) = command else {
+                    continue;
+                };
+                let s_type = match section.s_type {
+                    Some(OutputSectionType::DSect) => "DSECT",
+                    Some(OutputSectionType::Copy) => "COPY",
+                    _ => continue,
+                };
+                diagnostics.push(self.diagnostic(
+                    format!(
+                        "section `{}` uses the deprecated ({}) type, use (INFO) instead",
+                        section.name, s_type
+                    ),
+                    section.span(),
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lint::*;
+
+    const SCRIPT: &str = "SECTIONS {
+    .vectors : { *(.isr_vector) }
+    .text : {
+        *(.text*)
+        . = ALIGN(6);
+    }
+    .init_array : ALIGN(4) {
+        KEEP(*(SORT(.init_array.*)))
+        KEEP(*(.init_array))
+    }
+    .ARM.exidx : { *(.ARM.exidx*) }
+    .info (COPY) : { *(.info) }
+}
+";
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<(&str, usize)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.code, d.span.unwrap().line))
+            .collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let diagnostics = Linter::new().check_source(SCRIPT).unwrap();
+        assert_eq!(
+            codes(&diagnostics),
+            vec![
+                ("missing-keep", 2),
+                ("align-power-of-two", 5),
+                ("init-array-order", 8),
+                ("deprecated-section-type", 12),
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn test_configuration() {
+        let linter = Linter::new()
+            .enable("missing-exidx-discard")
+            .and_then(|linter| linter.disable("missing-keep"))
+            .and_then(|linter| linter.severity("align-power-of-two", Severity::Error))
+            .unwrap();
+        let diagnostics = linter.check_source(SCRIPT).unwrap();
+        assert_eq!(
+            codes(&diagnostics),
+            vec![
+                ("align-power-of-two", 5),
+                ("init-array-order", 8),
+                ("missing-exidx-discard", 11),
+                ("deprecated-section-type", 12),
+            ]
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+
+        let script = crate::parse("SECTIONS { /DISCARD/ : { *(.ARM.exidx*) } }").unwrap();
+        assert_eq!(linter.check(&script), Vec::new());
+
+        assert_eq!(
+            Linter::new().enable("missing-exidx").err(),
+            Some(UnknownRule("missing-exidx".into()))
+        );
+        assert!(Linter::new().disable("all").is_err());
+        assert!(Linter::empty()
+            .severity("missing-keep", Severity::Error)
+            .is_err());
+    }
+
+    #[test]
+    fn test_missing_keep() {
+        let diagnostics = Linter::new()
+            .check_source(
+                "SECTIONS {
+    .text : { *(.text.isr_handler) *(.misra) }
+    .data : { *(.data.vectors) }
+    .table : { *(.vector_table*) }
+}",
+            )
+            .unwrap();
+        assert_eq!(codes(&diagnostics), vec![("missing-keep", 4)]);
+    }
+
+    #[test]
+    fn test_missing_exidx_discard() {
+        let linter = Linter::new().enable("missing-exidx-discard").unwrap();
+        let check = |source: &str| {
+            let diagnostics = linter.check_source(source).unwrap();
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            check("SECTIONS { /DISCARD/ : { *(.ARM.exidx*) } .text : { *(.text) } }"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            check("SECTIONS { /DISCARD/ : { main.o(.ARM.exidx*) } .text : { *(.text) } }"),
+            vec!["`.ARM.exidx` is not discarded"]
+        );
+        assert_eq!(
+            check(
+                "SECTIONS {
+                    /DISCARD/ : { *crt0.o(.ARM.exidx*) }
+                    .exidx : { *(.ARM.exidx*) }
+                    /DISCARD/ : { *(.ARM.exidx*) }
+                }"
+            ),
+            vec!["`.ARM.exidx` is placed in `.exidx` instead of being discarded"]
+        );
+        assert_eq!(
+            check("SECTIONS { .all : { main.o } /DISCARD/ : { *(.ARM.exidx*) } }"),
+            vec!["`.ARM.exidx` is placed in `.all` instead of being discarded"]
+        );
+    }
+
+    #[test]
+    fn test_suppression() {
+        let source = SCRIPT
+            .replace(
+                "    .vectors",
+                "    /* ldlint: allow(missing-keep) */\n    .vectors",
+            )
+            .replace("ALIGN(6);", "ALIGN(6); /* ldlint: allow(all) */")
+            .replace(
+                "SECTIONS {",
+                "/* ldlint: allow-file(deprecated-section-type, init-array-order) */\nSECTIONS {",
+            );
+        let diagnostics = Linter::new().check_source(&source).unwrap();
+        assert_eq!(codes(&diagnostics), Vec::new());
+
+        let source = "SECTIONS {
+  /* ldlint: allow(missing-keep) */
+
+  .v : { *(.vectors) }
+  ASSERT(1, \"/* ldlint: allow(missing-keep) */\");
+  .w : { *(.vectors) }
+}";
+        let diagnostics = Linter::new().check_source(source).unwrap();
+        assert_eq!(
+            codes(&diagnostics),
+            vec![("missing-keep", 4), ("missing-keep", 6)]
+        );
+    }
+}
@@ -0,0 +1,974 @@
+use crate::commands::{Command, InputFile};
+use crate::error::{IResult, ParseError};
+use crate::memory::{memory_command, MemoryCommand};
+use crate::script::{self, RootItem};
+use crate::sections::{
+    output_section_command, section_command, OutputSectionCommand, SectionCommand,
+};
+use crate::span::{SourceSpan, Span, Spanned, VisitSpans};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Access to script files, so that includes can be resolved from memory as
+/// well as from disk.
+pub trait FileLoader {
+    /// Reads a file, failing with `io::ErrorKind::NotFound` if it does not
+    /// exist
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Returns whether a file exists, even if it can't be read as text
+    fn exists(&self, path: &Path) -> bool {
+        !matches!(self.read(path), Err(error) if error.kind() == io::ErrorKind::NotFound)
+    }
+
+    /// Returns a path that is the same for every path to a file, used to
+    /// detect include cycles. Defaults to removing `.` and resolving `..`
+    /// components without looking at the files.
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        normalize(path)
+    }
+}
+
+/// Removes `.` components and the directory before each `..` from a path
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` of the root is the root
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Reads files from the filesystem.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsLoader;
+
+impl FileLoader for FsLoader {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    /// Resolves symbolic links as well, for files that exist
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| normalize(path))
+    }
+}
+
+/// Files kept in memory, by path. Paths with `.` or `..` components also find
+/// the file they lead to.
+impl FileLoader for HashMap<PathBuf, String> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.get(path)
+            .or_else(|| self.get(&normalize(path)))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not found"))
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// An included file was not found in any of the search directories
+    NotFound {
+        file: String,
+        included_from: PathBuf,
+    },
+    /// A file or library of `INPUT` or `GROUP` was not found
+    InputNotFound {
+        file: String,
+        script: PathBuf,
+    },
+    Parse {
+        path: PathBuf,
+        error: ParseError,
+    },
+    /// Files that include each other, starting and ending with the same file
+    IncludeCycle(Vec<PathBuf>),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::NotFound {
+                file,
+                included_from,
+            } => write!(
+                f,
+                "{}: cannot find included file `{}`",
+                included_from.display(),
+                file
+            ),
+            LoadError::InputNotFound { file, script } => {
+                write!(f, "{}: cannot find input file `{}`", script.display(), file)
+            }
+            LoadError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::IncludeCycle(files) => {
+                let files: Vec<_> = files
+                    .iter()
+                    .map(|file| file.display().to_string())
+                    .collect();
+                write!(f, "include cycle: {}", files.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { error, .. } => Some(error),
+            LoadError::Parse { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// File that the linker opens for an entry of `INPUT` or `GROUP`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct InputPath {
+    pub path: PathBuf,
+    /// Whether the entry is inside `AS_NEEDED`
+    pub as_needed: bool,
+}
+
+/// A file read while loading a script.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LoadedFile {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+/// A script with its includes expanded.
+#[derive(Debug, Clone)]
+pub struct LoadedScript {
+    /// Items with every `INCLUDE` replaced by the content of the file
+    pub items: Vec<RootItem>,
+    /// Files in the order they were first read, the root script first
+    pub files: Vec<LoadedFile>,
+    includes: Vec<IncludeSite>,
+}
+
+/// `INCLUDE` command of a loaded file, and the file it was resolved to
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct IncludeSite {
+    from: usize,
+    span: Span,
+    file: usize,
+}
+
+impl LoadedScript {
+    /// File a node of `items` was read from
+    pub fn file_of<N: Spanned>(&self, node: &N) -> Option<&LoadedFile> {
+        node.source_span().file().map(|file| &self.files[file])
+    }
+
+    /// Renders the script as a single file, with every `INCLUDE` replaced by
+    /// the source of the included file between `/* begin <path> */` and
+    /// `/* end <path> */` markers.
+    ///
+    /// Unlike generating `items`, this keeps the comments and formatting of
+    /// every file.
+    pub fn flatten(&self) -> String {
+        self.flatten_file(0)
+    }
+
+    fn flatten_file(&self, file: usize) -> String {
+        let source = &self.files[file].source;
+        let mut sites: Vec<&IncludeSite> = self
+            .includes
+            .iter()
+            .filter(|site| site.from == file)
+            .collect();
+        sites.sort_by_key(|site| site.span.start);
+
+        let mut output = String::new();
+        let mut last = 0;
+        for site in sites {
+            output.push_str(&source[last..site.span.start]);
+            // Keep the included lines at the indentation of the INCLUDE
+            let line_start = source[..site.span.start].rfind('\n').map_or(0, |i| i + 1);
+            let indentation = &source[line_start..site.span.start];
+            let indentation = if indentation.trim().is_empty() {
+                indentation
+            } else {
+                ""
+            };
+            let path = self.files[site.file].path.display();
+            let included = format!(
+                "/* begin {} */\n{}\n/* end {} */",
+                path,
+                self.flatten_file(site.file).trim(),
+                path
+            );
+            for (i, line) in included.lines().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                    if !line.is_empty() {
+                        output.push_str(indentation);
+                    }
+                }
+                output.push_str(line);
+            }
+            last = site.span.end;
+        }
+        output.push_str(&source[last..]);
+        output
+    }
+}
+
+/// Loads scripts and expands their `INCLUDE` commands, at the root of the
+/// script as well as inside `MEMORY`, `SECTIONS` and output sections.
+///
+/// Included files are looked up in the directory of the including file, in
+/// the search paths given to the loader, and in the directories named by
+/// `SEARCH_DIR` commands seen so far, in that order.
+///
+/// ```
+/// extern crate ldscript_parser as lds;
+///
+/// use std::collections::HashMap;
+/// use std::path::PathBuf;
+///
+/// let mut files = HashMap::new();
+/// files.insert(PathBuf::from("link.x"), "INCLUDE memory.x\nENTRY(main)".to_string());
+/// files.insert(
+///     PathBuf::from("lib/memory.x"),
+///     "MEMORY { RAM : ORIGIN = 0, LENGTH = 1K }".to_string(),
+/// );
+/// let script = lds::Loader::new(files).search_path("lib").load("link.x").unwrap();
+/// assert_eq!(script.items.len(), 2);
+/// let memory = script.file_of(&script.items[0]).unwrap();
+/// assert_eq!(memory.path, PathBuf::from("lib/memory.x"));
+/// ```
+pub struct Loader<L> {
+    files: L,
+    search_paths: Vec<PathBuf>,
+    sysroot: Option<PathBuf>,
+}
+
+impl<L: FileLoader> Loader<L> {
+    pub fn new(files: L) -> Loader<L> {
+        Loader {
+            files,
+            search_paths: Vec::new(),
+            sysroot: None,
+        }
+    }
+
+    /// Adds a directory to look for included files in, like `-L`
+    pub fn search_path<P: Into<PathBuf>>(mut self, path: P) -> Loader<L> {
+        self.search_paths.push(path.into());
+        self
+    }
+
+    /// Sets the directory that a leading `=` in paths refers to, like
+    /// `--sysroot`
+    pub fn sysroot<P: Into<PathBuf>>(mut self, path: P) -> Loader<L> {
+        self.sysroot = Some(path.into());
+        self
+    }
+
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<LoadedScript, LoadError> {
+        let path = path.as_ref().to_path_buf();
+        let source = self.files.read(&path).map_err(|error| LoadError::Io {
+            path: path.clone(),
+            error,
+        })?;
+        let mut expansion = Expansion {
+            loader: self,
+            files: vec![LoadedFile {
+                path: path.clone(),
+                source,
+            }],
+            search_dirs: Vec::new(),
+            includes: Vec::new(),
+            stack: vec![path],
+        };
+        let items = expansion.root_items(0)?;
+        Ok(LoadedScript {
+            items,
+            files: expansion.files,
+            includes: expansion.includes,
+        })
+    }
+}
+
+impl<L: FileLoader> Loader<L> {
+    /// Resolves the entries of the `INPUT` and `GROUP` commands of a script
+    /// to the files the linker would open, in order.
+    ///
+    /// `-lname` is looked up as `libname.so`, then `libname.a`, in each of
+    /// the search paths and then the `SEARCH_DIR`s of the script, and
+    /// `-l:file` as `file`. Other files are tried as given before the same
+    /// directories. Absolute paths in scripts inside the sysroot are looked
+    /// up in the sysroot first, as `=` paths always are.
+    ///
+    /// Entries that turn out to be linker scripts themselves, like `libc.so`
+    /// on most Linux systems, are replaced by the files they list.
+    pub fn input_files(&self, script: &LoadedScript) -> Result<Vec<InputPath>, LoadError> {
+        let mut inputs = Vec::new();
+        let mut resolution = Resolution {
+            stack: vec![script.files[0].path.clone()],
+            inputs: &mut inputs,
+        };
+        let directories: Vec<PathBuf> = self
+            .search_paths
+            .iter()
+            .map(|path| self.in_sysroot(&path.to_string_lossy()))
+            .collect();
+        self.script_inputs(script, directories, false, &mut resolution)?;
+        Ok(inputs)
+    }
+
+    fn script_inputs(
+        &self,
+        script: &LoadedScript,
+        mut directories: Vec<PathBuf>,
+        as_needed: bool,
+        resolution: &mut Resolution,
+    ) -> Result<(), LoadError> {
+        // SEARCH_DIR applies to the whole script, wherever it appears
+        for item in &script.items {
+            if let RootItem::Command(Command::SearchDir { path, .. }) = item {
+                directories.push(self.in_sysroot(path));
+            }
+        }
+        for item in &script.items {
+            if let RootItem::Command(Command::Input { files, .. } | Command::Group { files, .. }) =
+                item
+            {
+                for file in files {
+                    self.input_file(file, script, &directories, as_needed, resolution)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn input_file(
+        &self,
+        file: &InputFile,
+        script: &LoadedScript,
+        directories: &[PathBuf],
+        as_needed: bool,
+        resolution: &mut Resolution,
+    ) -> Result<(), LoadError> {
+        let script_path = &script.files[0].path;
+        let (name, candidates) = match file {
+            InputFile::AsNeeded(files) => {
+                for file in files {
+                    self.input_file(file, script, directories, true, resolution)?;
+                }
+                return Ok(());
+            }
+            InputFile::File(name) => {
+                let mut candidates = self.file_candidates(name, script_path);
+                if !name.starts_with('=') && !Path::new(name).is_absolute() {
+                    candidates.extend(directories.iter().map(|directory| directory.join(name)));
+                }
+                (name.clone(), candidates)
+            }
+            InputFile::Library(name) => {
+                let files = match name.strip_prefix(':') {
+                    Some(file) => vec![file.to_string()],
+                    None => vec![format!("lib{}.so", name), format!("lib{}.a", name)],
+                };
+                let candidates = directories
+                    .iter()
+                    .flat_map(|directory| files.iter().map(|file| directory.join(file)))
+                    .collect();
+                (format!("-l{}", name), candidates)
+            }
+        };
+        let path = candidates
+            .into_iter()
+            .find(|path| self.files.exists(path))
+            .ok_or_else(|| LoadError::InputNotFound {
+                file: name,
+                script: script_path.clone(),
+            })?;
+
+        match self.files.read(&path) {
+            Ok(source) if !is_binary(&source) => {
+                if let Some(start) = self.position(&resolution.stack, &path) {
+                    let mut cycle = resolution.stack[start..].to_vec();
+                    cycle.push(path);
+                    return Err(LoadError::IncludeCycle(cycle));
+                }
+                let nested = self.load(&path)?;
+                resolution.stack.push(path);
+                self.script_inputs(&nested, directories.to_vec(), as_needed, resolution)?;
+                resolution.stack.pop();
+            }
+            Ok(_) => resolution.inputs.push(InputPath { path, as_needed }),
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                resolution.inputs.push(InputPath { path, as_needed })
+            }
+            Err(error) => return Err(LoadError::Io { path, error }),
+        }
+        Ok(())
+    }
+
+    /// Paths to try for a file name of `INPUT` or `GROUP` before the search
+    /// directories
+    fn file_candidates(&self, name: &str, script: &Path) -> Vec<PathBuf> {
+        if name.starts_with('=') {
+            return vec![self.in_sysroot(name)];
+        }
+        let path = PathBuf::from(name);
+        match &self.sysroot {
+            Some(sysroot) if path.is_absolute() && script.starts_with(sysroot) => {
+                vec![self.in_sysroot(&format!("={}", name)), path]
+            }
+            _ => vec![path],
+        }
+    }
+
+    /// Position of `path` in a stack of files being expanded, comparing
+    /// canonical paths so that `..` can't hide a cycle
+    fn position(&self, stack: &[PathBuf], path: &Path) -> Option<usize> {
+        let canonical = self.files.canonicalize(path);
+        stack
+            .iter()
+            .position(|file| self.files.canonicalize(file) == canonical)
+    }
+
+    /// Replaces a leading `=` with the sysroot
+    fn in_sysroot(&self, path: &str) -> PathBuf {
+        match path.strip_prefix('=') {
+            Some(path) => match &self.sysroot {
+                Some(sysroot) => sysroot.join(path.trim_start_matches('/')),
+                None => PathBuf::from(path),
+            },
+            None => PathBuf::from(path),
+        }
+    }
+}
+
+struct Resolution<'a> {
+    /// Scripts being resolved, to detect cycles
+    stack: Vec<PathBuf>,
+    inputs: &'a mut Vec<InputPath>,
+}
+
+/// Returns whether a file is an ELF object or an archive rather than a
+/// script
+fn is_binary(source: &str) -> bool {
+    ["\x7fELF", "!<arch>\n", "!<thin>\n"]
+        .iter()
+        .any(|magic| source.starts_with(magic))
+}
+
+struct Expansion<'a, L> {
+    loader: &'a Loader<L>,
+    files: Vec<LoadedFile>,
+    /// Directories from `SEARCH_DIR` commands
+    search_dirs: Vec<PathBuf>,
+    includes: Vec<IncludeSite>,
+    /// Files being expanded, to detect cycles
+    stack: Vec<PathBuf>,
+}
+
+impl<L: FileLoader> Expansion<'_, L> {
+    fn parse<O: VisitSpans>(
+        &self,
+        file: usize,
+        parser: fn(&str) -> IResult<&str, Vec<O>>,
+    ) -> Result<Vec<O>, LoadError> {
+        let loaded = &self.files[file];
+        let mut nodes =
+            crate::parse_with(parser, &loaded.source).map_err(|error| LoadError::Parse {
+                path: loaded.path.clone(),
+                error,
+            })?;
+        nodes.visit_spans(&mut |span| span.set_file(file));
+        Ok(nodes)
+    }
+
+    fn root_items(&mut self, file: usize) -> Result<Vec<RootItem>, LoadError> {
+        let mut items = Vec::new();
+        for item in self.parse(file, script::parse)? {
+            match item {
+                RootItem::Command(Command::Include { file: name, span }) => {
+                    items.extend(self.include(&name, span, file, Self::root_items)?)
+                }
+                RootItem::Memory { commands, span } => items.push(RootItem::Memory {
+                    commands: self.memory_commands(commands, file)?,
+                    span,
+                }),
+                RootItem::Sections { list, span } => items.push(RootItem::Sections {
+                    list: self.section_commands(list, file)?,
+                    span,
+                }),
+                item => {
+                    if let RootItem::Command(command) = &item {
+                        self.add_search_dir(command);
+                    }
+                    items.push(item);
+                }
+            }
+        }
+        Ok(items)
+    }
+
+    fn memory_file(&mut self, file: usize) -> Result<Vec<MemoryCommand>, LoadError> {
+        let commands = self.parse(file, |input| script::parse_items(memory_command, input))?;
+        self.memory_commands(commands, file)
+    }
+
+    fn memory_commands(
+        &mut self,
+        commands: Vec<MemoryCommand>,
+        file: usize,
+    ) -> Result<Vec<MemoryCommand>, LoadError> {
+        let mut expanded = Vec::new();
+        for command in commands {
+            match command {
+                MemoryCommand::Include { file: name, span } => {
+                    expanded.extend(self.include(&name, span, file, Self::memory_file)?)
+                }
+                command => expanded.push(command),
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn section_file(&mut self, file: usize) -> Result<Vec<SectionCommand>, LoadError> {
+        let list = self.parse(file, |input| script::parse_items(section_command, input))?;
+        self.section_commands(list, file)
+    }
+
+    fn section_commands(
+        &mut self,
+        list: Vec<SectionCommand>,
+        file: usize,
+    ) -> Result<Vec<SectionCommand>, LoadError> {
+        let mut commands = Vec::new();
+        for command in list {
+            match command {
+                SectionCommand::Command(Command::Include { file: name, span }) => {
+                    commands.extend(self.include(&name, span, file, Self::section_file)?)
+                }
+                SectionCommand::OutputSection(mut section) => {
+                    section.content = self.output_section_commands(section.content, file)?;
+                    commands.push(SectionCommand::OutputSection(section));
+                }
+                SectionCommand::Overlay(mut overlay) => {
+                    for section in &mut overlay.sections {
+                        let content = std::mem::take(&mut section.content);
+                        section.content = self.output_section_commands(content, file)?;
+                    }
+                    commands.push(SectionCommand::Overlay(overlay));
+                }
+                command => commands.push(command),
+            }
+        }
+        Ok(commands)
+    }
+
+    fn output_section_file(&mut self, file: usize) -> Result<Vec<OutputSectionCommand>, LoadError> {
+        let content = self.parse(file, |input| {
+            script::parse_items(output_section_command, input)
+        })?;
+        self.output_section_commands(content, file)
+    }
+
+    fn output_section_commands(
+        &mut self,
+        content: Vec<OutputSectionCommand>,
+        file: usize,
+    ) -> Result<Vec<OutputSectionCommand>, LoadError> {
+        let mut expanded = Vec::new();
+        for command in content {
+            match command {
+                OutputSectionCommand::Include { file: name, span } => {
+                    expanded.extend(self.include(&name, span, file, Self::output_section_file)?)
+                }
+                command => expanded.push(command),
+            }
+        }
+        Ok(expanded)
+    }
+
+    fn add_search_dir(&mut self, command: &Command) {
+        if let Command::SearchDir { path, .. } = command {
+            self.search_dirs.push(self.loader.in_sysroot(path));
+        }
+    }
+
+    /// Reads an included file and expands it with `expand`
+    fn include<O>(
+        &mut self,
+        name: &str,
+        span: SourceSpan,
+        from: usize,
+        expand: fn(&mut Self, usize) -> Result<Vec<O>, LoadError>,
+    ) -> Result<Vec<O>, LoadError> {
+        let (path, source) = self.find(name, from)?;
+        if let Some(start) = self.loader.position(&self.stack, &path) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(path);
+            return Err(LoadError::IncludeCycle(cycle));
+        }
+        let canonical = self.loader.files.canonicalize(&path);
+        let file = match self
+            .files
+            .iter()
+            .position(|file| self.loader.files.canonicalize(&file.path) == canonical)
+        {
+            Some(file) => file,
+            None => {
+                self.files.push(LoadedFile {
+                    path: path.clone(),
+                    source,
+                });
+                self.files.len() - 1
+            }
+        };
+        if let Some(span) = span.get() {
+            let site = IncludeSite { from, span, file };
+            if !self.includes.contains(&site) {
+                self.includes.push(site);
+            }
+        }
+        self.stack.push(path);
+        let items = expand(self, file)?;
+        self.stack.pop();
+        Ok(items)
+    }
+
+    fn find(&self, name: &str, from: usize) -> Result<(PathBuf, String), LoadError> {
+        let including = &self.files[from].path;
+        let directories: Vec<PathBuf> = if Path::new(name).is_absolute() {
+            vec![PathBuf::new()]
+        } else {
+            including
+                .parent()
+                .map(Path::to_path_buf)
+                .into_iter()
+                .chain(self.loader.search_paths.iter().cloned())
+                .chain(self.search_dirs.iter().cloned())
+                .collect()
+        };
+        for directory in directories {
+            let path = directory.join(name);
+            match self.loader.files.read(&path) {
+                Ok(source) => return Ok((path, source)),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(LoadError::Io { path, error }),
+            }
+        }
+        Err(LoadError::NotFound {
+            file: name.into(),
+            included_from: including.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::loader::*;
+
+    fn files(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_includes() {
+        let files = files(&[
+            (
+                "boards/link.x",
+                "SEARCH_DIR(common)\nINCLUDE memory.x\nSECTIONS {\n  INCLUDE text.x\n  .bss : { *(.bss) }\n}",
+            ),
+            ("boards/memory.x", "MEMORY { RAM : ORIGIN = 0, LENGTH = 1K }"),
+            ("common/text.x", ".text : { *(.text) }\nINCLUDE rodata.x"),
+            ("vendor/rodata.x", ".rodata : { *(.rodata) }"),
+        ]);
+        let script = Loader::new(files)
+            .search_path("vendor")
+            .load("boards/link.x")
+            .unwrap();
+
+        let expected = crate::parse(
+            "SEARCH_DIR(common)
+            MEMORY { RAM : ORIGIN = 0, LENGTH = 1K }
+            SECTIONS {
+                .text : { *(.text) }
+                .rodata : { *(.rodata) }
+                .bss : { *(.bss) }
+            }",
+        )
+        .unwrap();
+        assert_eq!(script.items, expected);
+
+        let paths: Vec<_> = script.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("boards/link.x"),
+                PathBuf::from("boards/memory.x"),
+                PathBuf::from("common/text.x"),
+                PathBuf::from("vendor/rodata.x"),
+            ]
+        );
+        let RootItem::Sections { list, .. } = &script.items[2] else {
+            panic!("expected SECTIONS");
+        };
+        let origins: Vec<_> = list
+            .iter()
+            .map(|command| script.file_of(command).unwrap().path.clone())
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                PathBuf::from("common/text.x"),
+                PathBuf::from("vendor/rodata.x"),
+                PathBuf::from("boards/link.x"),
+            ]
+        );
+        assert_eq!(list[1].span().unwrap().line, 1);
+    }
+
+    #[test]
+    fn test_block_includes() {
+        let files = files(&[
+            (
+                "link.x",
+                "MEMORY {\n  INCLUDE flash.x\n  RAM : ORIGIN = 0x20000000, LENGTH = 8K\n}\n\
+                 SECTIONS {\n  .text : { INCLUDE text.x } > FLASH\n  \
+                 OVERLAY : { .a { INCLUDE text.x } }\n}",
+            ),
+            ("flash.x", "FLASH : ORIGIN = 0, LENGTH = 64K"),
+            ("text.x", "KEEP(*(.vectors))\n*(.text*)"),
+        ]);
+        let script = Loader::new(files).load("link.x").unwrap();
+        let expected = crate::parse(
+            "MEMORY {
+                FLASH : ORIGIN = 0, LENGTH = 64K
+                RAM : ORIGIN = 0x20000000, LENGTH = 8K
+            }
+            SECTIONS {
+                .text : { KEEP(*(.vectors)) *(.text*) } > FLASH
+                OVERLAY : { .a { KEEP(*(.vectors)) *(.text*) } }
+            }",
+        )
+        .unwrap();
+        assert_eq!(script.items, expected);
+        assert_eq!(script.files.len(), 3);
+
+        let RootItem::Memory { commands, .. } = &script.items[0] else {
+            panic!("expected MEMORY");
+        };
+        assert_eq!(
+            script.file_of(&commands[0]).unwrap().path,
+            PathBuf::from("flash.x")
+        );
+    }
+
+    #[test]
+    fn test_flatten() {
+        let files = files(&[
+            (
+                "link.x",
+                "INCLUDE defs.x\n\
+                 MEMORY\n{\n  INCLUDE memory.x\n}\n\
+                 SECTIONS\n{\n  INCLUDE vectors.x\n  .text : { INCLUDE text.x } > FLASH\n}\n",
+            ),
+            ("defs.x", "/* stack */\n_stack_size = 0x400;\n"),
+            (
+                "memory.x",
+                "FLASH : ORIGIN = 0, LENGTH = 64K\nRAM : ORIGIN = 0x20000000, LENGTH = 8K",
+            ),
+            (
+                "vectors.x",
+                ".vectors : {\n  KEEP(*(.vectors))\n} > FLASH\n",
+            ),
+            ("text.x", "*(.text*)"),
+        ]);
+        let script = Loader::new(files).load("link.x").unwrap();
+        let flattened = script.flatten();
+        assert_eq!(
+            flattened,
+            "/* begin defs.x */
+/* stack */
+_stack_size = 0x400;
+/* end defs.x */
+MEMORY
+{
+  /* begin memory.x */
+  FLASH : ORIGIN = 0, LENGTH = 64K
+  RAM : ORIGIN = 0x20000000, LENGTH = 8K
+  /* end memory.x */
+}
+SECTIONS
+{
+  /* begin vectors.x */
+  .vectors : {
+    KEEP(*(.vectors))
+  } > FLASH
+  /* end vectors.x */
+  .text : { /* begin text.x */
+*(.text*)
+/* end text.x */ } > FLASH
+}
+"
+        );
+        assert_eq!(crate::parse(&flattened).unwrap(), script.items);
+    }
+
+    #[test]
+    fn test_input_files() {
+        let loader = Loader::new(files(&[
+            (
+                "link.x",
+                "SEARCH_DIR(\"=/usr/lib\")\nINPUT(crt0.o -lm)\nGROUP(-lc -l:libgcc.a)",
+            ),
+            ("crt0.o", "\x7fELF"),
+            ("/sysroot/usr/lib/libm.so", "\x7fELF"),
+            ("/sysroot/usr/lib/libm.a", "!<arch>\n"),
+            (
+                "/sysroot/usr/lib/libc.so",
+                "/* GNU ld script */\n\
+                 OUTPUT_FORMAT(elf64-x86-64)\n\
+                 GROUP ( /lib/libc.so.6 /usr/lib/libc_nonshared.a \
+                 AS_NEEDED ( /lib/ld-linux-x86-64.so.2 ) )",
+            ),
+            ("/sysroot/lib/libc.so.6", "\x7fELF"),
+            ("/sysroot/usr/lib/libc_nonshared.a", "!<arch>\n"),
+            ("/sysroot/lib/ld-linux-x86-64.so.2", "\x7fELF"),
+            ("gcc/libgcc.a", "!<arch>\n"),
+        ]))
+        .search_path("gcc")
+        .sysroot("/sysroot");
+        let script = loader.load("link.x").unwrap();
+        let inputs = loader.input_files(&script).unwrap();
+        let input = |path: &str, as_needed| InputPath {
+            path: PathBuf::from(path),
+            as_needed,
+        };
+        assert_eq!(
+            inputs,
+            vec![
+                input("crt0.o", false),
+                input("/sysroot/usr/lib/libm.so", false),
+                input("/sysroot/lib/libc.so.6", false),
+                input("/sysroot/usr/lib/libc_nonshared.a", false),
+                input("/sysroot/lib/ld-linux-x86-64.so.2", true),
+                input("gcc/libgcc.a", false),
+            ]
+        );
+
+        let loader = Loader::new(files(&[
+            ("missing.x", "INPUT(crt0.o)\nGROUP(-lmissing)"),
+            ("crt0.o", "\x7fELF"),
+            ("loop.x", "INPUT(libloop.so)"),
+            ("libloop.so", "GROUP(loop.x)"),
+        ]));
+        let script = loader.load("missing.x").unwrap();
+        assert_eq!(
+            loader.input_files(&script).unwrap_err().to_string(),
+            "missing.x: cannot find input file `-lmissing`"
+        );
+        let script = loader.load("loop.x").unwrap();
+        assert!(matches!(
+            loader.input_files(&script),
+            Err(LoadError::IncludeCycle(_))
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        let loader = Loader::new(files(&[
+            ("a.x", "INCLUDE b.x"),
+            ("b.x", "SECTIONS { INCLUDE a.x }"),
+            ("c.x", "INCLUDE missing.x"),
+            ("d.x", "INCLUDE e.x"),
+            ("e.x", "SECTIONS {"),
+        ]));
+        match loader.load("a.x") {
+            Err(LoadError::IncludeCycle(cycle)) => assert_eq!(
+                cycle,
+                vec![
+                    PathBuf::from("a.x"),
+                    PathBuf::from("b.x"),
+                    PathBuf::from("a.x")
+                ]
+            ),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+
+        let loader = Loader::new(files(&[
+            ("dir/a.x", "INCLUDE ../dir/b.x"),
+            ("dir/b.x", "INCLUDE ./../dir/a.x"),
+            ("lib/loop.x", "INPUT(lib/../lib/./loop.x)"),
+        ]));
+        match loader.load("dir/a.x") {
+            Err(LoadError::IncludeCycle(cycle)) => assert_eq!(
+                cycle,
+                vec![
+                    PathBuf::from("dir/a.x"),
+                    PathBuf::from("dir/../dir/b.x"),
+                    PathBuf::from("dir/../dir/./../dir/a.x")
+                ]
+            ),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+        let script = loader.load("lib/loop.x").unwrap();
+        assert!(matches!(
+            loader.input_files(&script),
+            Err(LoadError::IncludeCycle(_))
+        ));
+        assert_eq!(
+            normalize(Path::new("./a/b/../../../c/./d")),
+            PathBuf::from("../c/d")
+        );
+        assert_eq!(normalize(Path::new("/../a/..")), PathBuf::from("/"));
+
+        let loader = Loader::new(files(&[
+            ("a.x", "INCLUDE b.x"),
+            ("b.x", "SECTIONS { INCLUDE a.x }"),
+            ("c.x", "INCLUDE missing.x"),
+            ("d.x", "INCLUDE e.x"),
+            ("e.x", "SECTIONS {"),
+        ]));
+        assert_eq!(
+            loader.load("c.x").unwrap_err().to_string(),
+            "c.x: cannot find included file `missing.x`"
+        );
+        match loader.load("d.x") {
+            Err(LoadError::Parse { path, error }) => {
+                assert_eq!(path, PathBuf::from("e.x"));
+                assert_eq!(error.line, 1);
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        assert!(matches!(
+            loader.load("missing.x"),
+            Err(LoadError::Io { .. })
+        ));
+    }
+}
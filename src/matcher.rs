@@ -0,0 +1,490 @@
+use crate::layout::InputSection;
+use crate::script::RootItem;
+use crate::sections::{InputSectionFlags, OutputSectionCommand, SectionCommand, SectionPattern};
+
+/// Output section that an input section is assigned to, with the input
+/// section description and the pattern that claimed it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SectionMatch<'a> {
+    pub output_section: &'a str,
+    pub description: &'a OutputSectionCommand,
+    /// Section pattern that matched, or the file pattern of a description
+    /// without a section list
+    pub pattern: &'a SectionPattern,
+}
+
+/// Finds the output section the linker would place an input section in.
+///
+/// The first input section description in script order that matches wins,
+/// whether it is wrapped in `KEEP` or not. Sections matched by `/DISCARD/`
+/// are reported like any other output section.
+pub fn find_output_section<'a>(
+    items: &'a [RootItem],
+    input: &InputSection,
+) -> Option<SectionMatch<'a>> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            RootItem::Sections { list, .. } => Some(list),
+            _ => None,
+        })
+        .flatten()
+        .flat_map(|command| match command {
+            SectionCommand::OutputSection(section) => vec![(&section.name, &section.content)],
+            SectionCommand::Overlay(overlay) => overlay
+                .sections
+                .iter()
+                .map(|section| (&section.name, &section.content))
+                .collect(),
+            _ => Vec::new(),
+        })
+        .find_map(|(name, content)| {
+            content.iter().find_map(|description| {
+                Some(SectionMatch {
+                    output_section: name,
+                    description,
+                    pattern: match_description(description, input)?,
+                })
+            })
+        })
+}
+
+/// Matches an input section against an input section description, returning
+/// the pattern that matched. A description without a section list takes
+/// every section of the files it matches, and one with `INPUT_SECTION_FLAGS`
+/// only sections whose flags satisfy it.
+pub fn match_description<'a>(
+    description: &'a OutputSectionCommand,
+    input: &InputSection,
+) -> Option<&'a SectionPattern> {
+    let (flags, file, sections) = match description {
+        OutputSectionCommand::InputSection {
+            flags,
+            file,
+            sections,
+            ..
+        }
+        | OutputSectionCommand::KeepInputSection {
+            flags,
+            file,
+            sections,
+            ..
+        } => (flags, file, sections),
+        _ => return None,
+    };
+    if flags.as_ref().is_some_and(|flags| !flags.matches(input)) {
+        return None;
+    }
+    if !file.matches_file(input) {
+        return None;
+    }
+    if sections.is_empty() {
+        return Some(file);
+    }
+    sections
+        .iter()
+        .find(|pattern| pattern.matches_section(input))
+}
+
+impl SectionPattern {
+    /// Wildcard of the pattern, without the sorting or exclusions around it
+    pub fn wildcard(&self) -> &str {
+        match self {
+            SectionPattern::Simple(text) => text,
+            SectionPattern::SortByName(pattern)
+            | SectionPattern::SortByAlignment(pattern)
+            | SectionPattern::SortByInitPriority(pattern)
+            | SectionPattern::SortNone(pattern)
+            | SectionPattern::Reverse(pattern)
+            | SectionPattern::ExcludeFile { pattern, .. } => pattern.wildcard(),
+        }
+    }
+
+    /// Keys the matches of the pattern are sorted by, outermost first, each
+    /// with whether it is reversed. Empty if the matches keep their input
+    /// order.
+    pub fn sort_keys(&self) -> Vec<(SortKey, bool)> {
+        match self {
+            SectionPattern::Simple(_) | SectionPattern::SortNone(_) => Vec::new(),
+            SectionPattern::ExcludeFile { pattern, .. } => pattern.sort_keys(),
+            SectionPattern::SortByName(pattern) => sorted_by(SortKey::Name, pattern),
+            SectionPattern::SortByAlignment(pattern) => sorted_by(SortKey::Alignment, pattern),
+            SectionPattern::SortByInitPriority(pattern) => {
+                sorted_by(SortKey::InitPriority, pattern)
+            }
+            SectionPattern::Reverse(pattern) => {
+                let mut keys = pattern.sort_keys();
+                match keys.first_mut() {
+                    Some((_, reversed)) => *reversed = !*reversed,
+                    None => keys.push((SortKey::Name, true)),
+                }
+                keys
+            }
+        }
+    }
+
+    /// Matches the file part of an input section description against the
+    /// file an input section comes from.
+    ///
+    /// `archive:member` matches `member` inside `archive`, `archive:` any
+    /// member of it and `:file` only files outside of archives. A pattern
+    /// without `:` matches the object or member name, or the name of the
+    /// archive a member comes from.
+    pub fn matches_file(&self, input: &InputSection) -> bool {
+        if self.excludes(input) {
+            return false;
+        }
+        file_matches(self.wildcard(), input)
+    }
+
+    /// Matches a section pattern against the name of an input section,
+    /// honoring `EXCLUDE_FILE`.
+    pub fn matches_section(&self, input: &InputSection) -> bool {
+        !self.excludes(input) && wildcard(self.wildcard(), &input.name)
+    }
+
+    fn excludes(&self, input: &InputSection) -> bool {
+        match self {
+            SectionPattern::Simple(_) => false,
+            SectionPattern::ExcludeFile { files, pattern } => {
+                files.iter().any(|file| file_matches(file, input)) || pattern.excludes(input)
+            }
+            SectionPattern::SortByName(pattern)
+            | SectionPattern::SortByAlignment(pattern)
+            | SectionPattern::SortByInitPriority(pattern)
+            | SectionPattern::SortNone(pattern)
+            | SectionPattern::Reverse(pattern) => pattern.excludes(input),
+        }
+    }
+}
+
+impl InputSectionFlags {
+    /// Checks the `sh_flags` of an input section against the flags. A flag
+    /// name that is not a known `SHF_*` constant never matches, as `ld`
+    /// rejects the script.
+    pub fn matches(&self, input: &InputSection) -> bool {
+        let value = |names: &[String]| {
+            names
+                .iter()
+                .try_fold(0, |value, name| Some(value | section_flag(name)?))
+        };
+        match (value(&self.required), value(&self.excluded)) {
+            (Some(required), Some(excluded)) => {
+                input.flags & required == required && input.flags & excluded == 0
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Value of an ELF section flag accepted by `INPUT_SECTION_FLAGS`
+fn section_flag(name: &str) -> Option<u64> {
+    Some(match name {
+        "SHF_WRITE" => 0x1,
+        "SHF_ALLOC" => 0x2,
+        "SHF_EXECINSTR" => 0x4,
+        "SHF_MERGE" => 0x10,
+        "SHF_STRINGS" => 0x20,
+        "SHF_INFO_LINK" => 0x40,
+        "SHF_LINK_ORDER" => 0x80,
+        "SHF_OS_NONCONFORMING" => 0x100,
+        "SHF_GROUP" => 0x200,
+        "SHF_TLS" => 0x400,
+        "SHF_COMPRESSED" => 0x800,
+        "SHF_GNU_RETAIN" => 0x20_0000,
+        "SHF_EXCLUDE" => 0x8000_0000,
+        _ => return None,
+    })
+}
+
+/// Property that `SORT_*` orders input sections by.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SortKey {
+    Name,
+    /// Descending alignment
+    Alignment,
+    /// Numeric suffix of `.init_array.NNNNN` style names
+    InitPriority,
+}
+
+fn sorted_by(key: SortKey, inner: &SectionPattern) -> Vec<(SortKey, bool)> {
+    // REVERSE of an unsorted pattern reverses this key instead of adding one
+    if let SectionPattern::Reverse(pattern) = inner {
+        if pattern.sort_keys().is_empty() {
+            return vec![(key, true)];
+        }
+    }
+    let mut keys = vec![(key, false)];
+    // SORT_NONE and nested keys are secondary
+    keys.extend(inner.sort_keys());
+    keys
+}
+
+fn file_matches(pattern: &str, input: &InputSection) -> bool {
+    let Some((archive, member)) = split_archive(pattern) else {
+        return wildcard(pattern, &input.file)
+            || input
+                .archive
+                .as_deref()
+                .is_some_and(|archive| wildcard(pattern, archive));
+    };
+    match &input.archive {
+        _ if archive.is_empty() => input.archive.is_none() && wildcard(member, &input.file),
+        Some(name) => {
+            wildcard(archive, name) && (member.is_empty() || wildcard(member, &input.file))
+        }
+        None => false,
+    }
+}
+
+/// Splits `archive:member` at the first colon outside of a bracket
+/// expression.
+fn split_archive(pattern: &str) -> Option<(&str, &str)> {
+    let mut in_class = false;
+    for (i, c) in pattern.char_indices() {
+        match c {
+            '[' => in_class = true,
+            ']' => in_class = false,
+            ':' if !in_class => return Some((&pattern[..i], &pattern[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Matches `text` against a shell wildcard the way `fnmatch` does without
+/// flags: `*` and `?` match any character including `/`, `[...]` classes
+/// take ranges and `!` or `^` negation, and `\` escapes the next character.
+pub(crate) fn wildcard(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match class(&pattern[p..], text[t]) {
+                Some((matched, length)) => matched.then_some(length),
+                None => (text[t] == '[').then_some(1),
+            },
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == text[t]).then_some(2),
+            Some(&c) => (c == text[t]).then_some(1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(length), _) => {
+                p += length;
+                t += 1;
+            }
+            (None, Some((star, matched))) => {
+                backtrack = Some((star, matched + 1));
+                p = star + 1;
+                t = matched + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches a character against the bracket expression at the start of
+/// `pattern`. Returns whether it matched and the length of the expression,
+/// or `None` if the expression is not terminated.
+fn class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let start = *pattern.get(i)?;
+        if start == ']' && !first {
+            return Some((matched != negated, i + 1));
+        }
+        first = false;
+        match (pattern.get(i + 1), pattern.get(i + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                matched |= start <= c && c <= end;
+                i += 3;
+            }
+            _ => {
+                matched |= start == c;
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matcher::*;
+
+    #[test]
+    fn test_wildcard() {
+        assert!(wildcard("*", ""));
+        assert!(wildcard(".text.*", ".text.main"));
+        assert!(!wildcard(".text.*", ".text"));
+        assert!(wildcard("*crt?.o", "/usr/lib/crt1.o"));
+        assert!(!wildcard("*crt?.o", "crt10.o"));
+        assert!(wildcard("[A-Z]*", "Main.o"));
+        assert!(!wildcard("[A-Z]*", "main.o"));
+        assert!(wildcard("[!a-z]*", "_start.o"));
+        assert!(wildcard("[^a-z]*", "0.o"));
+        assert!(wildcard("[]x]", "]"));
+        assert!(wildcard("a[", "a["));
+        assert!(wildcard("\\*", "*"));
+        assert!(!wildcard("\\*", "a"));
+    }
+
+    #[test]
+    fn test_files() {
+        let object = InputSection::new("startup.o", ".text", 4);
+        let member = InputSection {
+            archive: Some("/usr/lib/libdrv.a".into()),
+            ..InputSection::new("uart.o", ".text.foo", 4)
+        };
+        let file = |pattern: &str| SectionPattern::Simple(pattern.into());
+
+        assert!(file("*").matches_file(&member));
+        assert!(file("uart.o").matches_file(&member));
+        assert!(file("*libdrv.a:").matches_file(&member));
+        assert!(file("*libdrv.a:uart.o").matches_file(&member));
+        assert!(file("*libdrv.a:u*").matches_file(&member));
+        assert!(!file("*libdrv.a:").matches_file(&object));
+        assert!(!file(":uart.o").matches_file(&member));
+        assert!(file(":startup.o").matches_file(&object));
+        assert!(!file("*libc.a:*").matches_file(&member));
+        assert!(file("*libdrv.a").matches_file(&member));
+        assert!(file("/usr/lib/libdrv.a").matches_file(&member));
+        assert!(!file("*libdrv.a").matches_file(&object));
+
+        let excluded = SectionPattern::ExcludeFile {
+            files: vec!["*libdrv.a:*".into()],
+            pattern: Box::new(file(".text*")),
+        };
+        assert!(!excluded.matches_section(&member));
+        assert!(excluded.matches_section(&object));
+        let excluded_archive = SectionPattern::ExcludeFile {
+            files: vec!["*libdrv.a".into()],
+            pattern: Box::new(file(".text*")),
+        };
+        assert!(!excluded_archive.matches_section(&member));
+        assert!(excluded_archive.matches_section(&object));
+        let sorted = SectionPattern::sort_by_name(excluded);
+        assert_eq!(sorted.wildcard(), ".text*");
+        assert!(!sorted.matches_section(&member));
+        assert!(sorted.matches_section(&object));
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let keys = |pattern: &str| {
+            let script = format!("SECTIONS {{ .a : {{ *({}) }} }}", pattern);
+            let items = crate::parse(&script).unwrap();
+            let RootItem::Sections { list, .. } = &items[0] else {
+                panic!("expected SECTIONS");
+            };
+            let SectionCommand::OutputSection(section) = &list[0] else {
+                panic!("expected an output section");
+            };
+            let OutputSectionCommand::InputSection { sections, .. } = &section.content[0] else {
+                panic!("expected an input section description");
+            };
+            sections[0].sort_keys()
+        };
+        assert_eq!(keys(".text"), vec![]);
+        assert_eq!(keys("SORT_NONE(.text)"), vec![]);
+        assert_eq!(
+            keys("SORT_BY_NAME(SORT_BY_ALIGNMENT(.text.*))"),
+            vec![(SortKey::Name, false), (SortKey::Alignment, false)]
+        );
+        assert_eq!(
+            keys("REVERSE(SORT_BY_INIT_PRIORITY(EXCLUDE_FILE(*crtbegin.o) .init_array.*))"),
+            vec![(SortKey::InitPriority, true)]
+        );
+        assert_eq!(keys("REVERSE(.text.*)"), vec![(SortKey::Name, true)]);
+        assert_eq!(
+            keys("SORT_BY_NAME(REVERSE(.data.*))"),
+            vec![(SortKey::Name, true)]
+        );
+        assert_eq!(
+            keys("SORT_BY_INIT_PRIORITY(REVERSE(.init_array.*))"),
+            vec![(SortKey::InitPriority, true)]
+        );
+        assert_eq!(
+            keys("SORT_BY_NAME(REVERSE(SORT_BY_ALIGNMENT(.data.*)))"),
+            vec![(SortKey::Name, false), (SortKey::Alignment, true)]
+        );
+    }
+
+    #[test]
+    fn test_find_output_section() {
+        let script = crate::parse(
+            "SECTIONS {
+                .isr : { KEEP(*(.isr_vector)) }
+                .fast : { *libdrv.a:uart.o(.text .text.*) }
+                .text : { *(EXCLUDE_FILE(*crtend.o) .text*) *(SORT(.rodata.*)) }
+                OVERLAY : { .ov { ov.o } }
+                /DISCARD/ : { *(.comment) }
+            }",
+        )
+        .unwrap();
+        let member = InputSection {
+            archive: Some("libdrv.a".into()),
+            ..InputSection::new("uart.o", ".text.foo", 4)
+        };
+        let found = find_output_section(&script, &member).unwrap();
+        assert_eq!(found.output_section, ".fast");
+        assert_eq!(found.pattern, &SectionPattern::Simple(".text.*".into()));
+
+        let find = |file: &str, name: &str| {
+            find_output_section(&script, &InputSection::new(file, name, 4))
+                .map(|found| found.output_section)
+        };
+        assert_eq!(find("main.o", ".isr_vector"), Some(".isr"));
+        assert_eq!(find("main.o", ".text.foo"), Some(".text"));
+        assert_eq!(find("crtend.o", ".text"), None);
+        assert_eq!(find("main.o", ".rodata.str1"), Some(".text"));
+        assert_eq!(find("ov.o", ".data"), Some(".ov"));
+        assert_eq!(find("main.o", ".comment"), Some("/DISCARD/"));
+        assert_eq!(find("main.o", ".data"), None);
+    }
+
+    #[test]
+    fn test_input_section_flags() {
+        let script = crate::parse(
+            "SECTIONS {
+                .text : { INPUT_SECTION_FLAGS(SHF_EXECINSTR) *(.data* .text*) }
+                .data : { KEEP(INPUT_SECTION_FLAGS(SHF_WRITE & !SHF_EXECINSTR) *(.data*)) }
+                .other : { INPUT_SECTION_FLAGS(SHF_UNKNOWN) *(.data*) }
+            }",
+        )
+        .unwrap();
+        let find = |name: &str, flags: u64| {
+            let input = InputSection {
+                flags,
+                ..InputSection::new("main.o", name, 4)
+            };
+            find_output_section(&script, &input).map(|found| found.output_section)
+        };
+        assert_eq!(find(".text.main", 0x6), Some(".text"));
+        assert_eq!(find(".text.main", 0x2), None);
+        assert_eq!(find(".data.rel", 0x3), Some(".data"));
+        assert_eq!(find(".data.rel", 0x7), Some(".text"));
+        assert_eq!(find(".data.ro", 0x2), None);
+
+        let flags: InputSectionFlags = "SHF_ALLOC & !SHF_WRITE".parse().unwrap();
+        assert!(flags.matches(&InputSection {
+            flags: 0x12,
+            ..InputSection::new("main.o", ".rodata", 4)
+        }));
+        assert!(!flags.matches(&InputSection::new("main.o", ".comment", 4)));
+    }
+}
@@ -1,23 +1,150 @@
+use super::commands::include_file;
+use super::error::{context, tag, IResult};
+use super::expressions::{expression, Expression};
 use super::idents::symbol;
-use super::numbers::number;
+use super::span::{spanned, SourceSpan};
 use super::whitespace::opt_space;
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_until},
-    combinator::opt,
-    sequence::{delimited, tuple},
-    IResult,
+    character::complete::anychar,
+    combinator::{cut, map, map_opt, opt},
+    multi::fold_many0,
+    sequence::{delimited, pair, tuple},
 };
+use std::ops::{BitOr, BitOrAssign};
+
+/// Entry of a `MEMORY` block.
+// Nearly every entry is a region, so boxing it would only add indirection
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq, Clone)]
+pub enum MemoryCommand {
+    Region(Region),
+    Include { file: String, span: SourceSpan },
+}
+
+impl MemoryCommand {
+    /// Returns the region declared by the entry, if it is not an include.
+    pub fn region(&self) -> Option<&Region> {
+        match self {
+            MemoryCommand::Region(region) => Some(region),
+            MemoryCommand::Include { .. } => None,
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Region {
     pub name: String,
-    pub origin: u64,
-    pub length: u64,
+    pub attributes: RegionAttributes,
+    pub origin: Expression,
+    pub length: Expression,
+    pub span: SourceSpan,
+}
+
+impl Region {
+    /// Returns the origin of the region if it is a constant expression.
+    pub fn origin_value(&self) -> Option<u64> {
+        self.origin.constant_value()
+    }
+
+    /// Returns the length of the region if it is a constant expression.
+    pub fn length_value(&self) -> Option<u64> {
+        self.length.constant_value()
+    }
+}
+
+/// Attributes of a memory region, as written between parentheses after its
+/// name. Flags that follow a `!` are kept in `inverted`.
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct RegionAttributes {
+    pub flags: RegionFlags,
+    pub inverted: RegionFlags,
+}
+
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RegionFlags(u8);
+
+impl RegionFlags {
+    /// Read-only section (`R`)
+    pub const READ: RegionFlags = RegionFlags(1 << 0);
+    /// Read/write section (`W`)
+    pub const WRITE: RegionFlags = RegionFlags(1 << 1);
+    /// Executable section (`X`)
+    pub const EXECUTE: RegionFlags = RegionFlags(1 << 2);
+    /// Allocatable section (`A`)
+    pub const ALLOCATE: RegionFlags = RegionFlags(1 << 3);
+    /// Initialized section (`I` or `L`)
+    pub const INITIALIZED: RegionFlags = RegionFlags(1 << 4);
+
+    pub const fn empty() -> Self {
+        RegionFlags(0)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: RegionFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    fn from_char(c: char) -> Option<RegionFlags> {
+        match c {
+            'r' | 'R' => Some(RegionFlags::READ),
+            'w' | 'W' => Some(RegionFlags::WRITE),
+            'x' | 'X' => Some(RegionFlags::EXECUTE),
+            'a' | 'A' => Some(RegionFlags::ALLOCATE),
+            'i' | 'I' | 'l' | 'L' => Some(RegionFlags::INITIALIZED),
+            _ => None,
+        }
+    }
 }
 
-fn attributes(input: &str) -> IResult<&str, &str> {
-    delimited(tag("("), take_until(")"), tag(")"))(input)
+impl BitOr for RegionFlags {
+    type Output = RegionFlags;
+
+    fn bitor(self, rhs: RegionFlags) -> RegionFlags {
+        RegionFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for RegionFlags {
+    fn bitor_assign(&mut self, rhs: RegionFlags) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Parses the flags of a region attribute list, without its parentheses
+pub(crate) fn attribute_list(input: &str) -> IResult<&str, RegionAttributes> {
+    map(
+        fold_many0(
+            alt((
+                map(tag("!"), |_| None),
+                map(map_opt(anychar, RegionFlags::from_char), Some),
+            )),
+            || (RegionAttributes::default(), false),
+            |(mut attributes, inverted), flag| match flag {
+                None => (attributes, !inverted),
+                Some(flag) if inverted => {
+                    attributes.inverted |= flag;
+                    (attributes, inverted)
+                }
+                Some(flag) => {
+                    attributes.flags |= flag;
+                    (attributes, inverted)
+                }
+            },
+        ),
+        |(attributes, _)| attributes,
+    )(input)
+}
+
+pub fn attributes(input: &str) -> IResult<&str, RegionAttributes> {
+    delimited(
+        pair(tag("("), opt_space),
+        attribute_list,
+        pair(opt_space, tag(")")),
+    )(input)
 }
 
 fn origin(input: &str) -> IResult<&str, &str> {
@@ -28,28 +155,49 @@ fn length(input: &str) -> IResult<&str, &str> {
     alt((tag("LENGTH"), tag("len"), tag("l")))(input)
 }
 
+pub fn memory_command(input: &str) -> IResult<&str, MemoryCommand> {
+    alt((
+        spanned(map(include_file, |file| MemoryCommand::Include {
+            file: file.into(),
+            span: SourceSpan::default(),
+        })),
+        map(region, MemoryCommand::Region),
+    ))(input)
+}
+
 pub fn region(input: &str) -> IResult<&str, Region> {
+    spanned(region_inner)(input)
+}
+
+fn region_inner(input: &str) -> IResult<&str, Region> {
     let (input, name) = symbol(input)?;
-    let (input, _) = tuple((
-        opt_space,
-        opt(attributes),
-        wsc!(tag(":")),
-        origin,
-        wsc!(tag("=")),
-    ))(input)?;
-    let (input, org) = number(input)?;
-    let (input, _) = tuple((wsc!(tag(",")), length, wsc!(tag("="))))(input)?;
-    let (input, len) = number(input)?;
+    let (input, _) = opt_space(input)?;
+    let (input, attributes) = opt(attributes)(input)?;
+    let (input, _) = wsc!(tag(":"))(input)?;
+    let (input, (origin, length)) = context(
+        move || format!("memory region `{}`", name),
+        cut(region_body),
+    )(input)?;
     Ok((
         input,
         Region {
             name: name.into(),
-            origin: org,
-            length: len,
+            attributes: attributes.unwrap_or_default(),
+            origin,
+            length,
+            span: SourceSpan::default(),
         },
     ))
 }
 
+fn region_body(input: &str) -> IResult<&str, (Expression, Expression)> {
+    let (input, _) = tuple((origin, wsc!(tag("="))))(input)?;
+    let (input, org) = expression(input)?;
+    let (input, _) = tuple((wsc!(tag(",")), length, wsc!(tag("="))))(input)?;
+    let (input, len) = expression(input)?;
+    Ok((input, (org, len)))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::memory::*;
@@ -60,17 +208,106 @@ mod tests {
             region("rom (rx)  : ORIGIN = 0, LENGTH = 256K"),
             Region {
                 name: "rom".into(),
-                origin: 0,
-                length: 256 * 1024,
+                attributes: RegionAttributes {
+                    flags: RegionFlags::READ | RegionFlags::EXECUTE,
+                    inverted: RegionFlags::empty(),
+                },
+                origin: Expression::Number(0),
+                length: Expression::Number(256 * 1024),
+                span: SourceSpan::default(),
             }
         );
         assert_done!(
             region("ram (!rx) : org = 0x40000000, l = 4M"),
             Region {
                 name: "ram".into(),
-                origin: 0x40000000,
-                length: 4 * 1024 * 1024,
+                attributes: RegionAttributes {
+                    flags: RegionFlags::empty(),
+                    inverted: RegionFlags::READ | RegionFlags::EXECUTE,
+                },
+                origin: Expression::Number(0x40000000),
+                length: Expression::Number(4 * 1024 * 1024),
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            region("flash : ORIGIN = 0, LENGTH = 1K"),
+            Region {
+                name: "flash".into(),
+                attributes: RegionAttributes::default(),
+                origin: Expression::Number(0),
+                length: Expression::Number(1024),
+                span: SourceSpan::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_region_expressions() {
+        let (_, r) = region("FLASH : ORIGIN = 0x8000000, LENGTH = 0x20000 - 0x400").unwrap();
+        assert_eq!(r.origin_value(), Some(0x8000000));
+        assert_eq!(r.length_value(), Some(0x20000 - 0x400));
+
+        let (_, r) = region("APP : ORIGIN = ORIGIN(FLASH) + 32K, LENGTH = __app_size").unwrap();
+        assert_eq!(r.origin_value(), None);
+        assert_eq!(r.length, Expression::Ident("__app_size".into()));
+
+        let (_, r) = region("RAM (rw) : o = 0x20000000, l = (16K << 1) * 2").unwrap();
+        assert_eq!(r.length_value(), Some(64 * 1024));
+    }
+
+    #[test]
+    fn test_memory_command() {
+        assert_done!(
+            memory_command("INCLUDE flash.ld"),
+            MemoryCommand::Include {
+                file: "flash.ld".into(),
+                span: SourceSpan::default(),
             }
         );
+        assert_done!(memory_command("INCLUDE ram.ld;"));
+        let (_, command) = memory_command("INCLUDE_RAM : ORIGIN = 0, LENGTH = 1K").unwrap();
+        assert_eq!(command.region().unwrap().name, "INCLUDE_RAM");
+    }
+
+    #[test]
+    fn test_attributes() {
+        assert_done!(
+            attributes("(RWXAI)"),
+            RegionAttributes {
+                flags: RegionFlags::READ
+                    | RegionFlags::WRITE
+                    | RegionFlags::EXECUTE
+                    | RegionFlags::ALLOCATE
+                    | RegionFlags::INITIALIZED,
+                inverted: RegionFlags::empty(),
+            }
+        );
+        assert_done!(
+            attributes("( wx!l )"),
+            RegionAttributes {
+                flags: RegionFlags::WRITE | RegionFlags::EXECUTE,
+                inverted: RegionFlags::INITIALIZED,
+            }
+        );
+        assert_fail!(attributes("(rq)"));
+
+        assert_eq!(
+            "rx".parse::<RegionAttributes>(),
+            Ok(RegionAttributes::from(
+                RegionFlags::READ | RegionFlags::EXECUTE
+            ))
+        );
+        assert_eq!(
+            " !w ".parse(),
+            Ok(RegionAttributes {
+                flags: RegionFlags::empty(),
+                inverted: RegionFlags::WRITE,
+            })
+        );
+        assert_eq!("".parse(), Ok(RegionAttributes::default()));
+        let err = "rwq".parse::<RegionAttributes>().unwrap_err();
+        assert_eq!((err.line, err.column), (1, 3));
+        assert!("r)".parse::<RegionAttributes>().is_err());
     }
 }
@@ -2,13 +2,15 @@
 
 use std::panic;
 
+use super::error::{expected, tag, IResult};
+
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while1},
+    bytes::complete::take_while1,
     character::complete::{hex_digit1, one_of},
     combinator::{map, map_res, opt},
     error::{ErrorKind, ParseError},
-    Err, IResult,
+    Err,
 };
 
 fn mul_suffix0(input: &str) -> IResult<&str, u64> {
@@ -78,7 +80,7 @@ fn suffixed_num(input: &str) -> IResult<&str, u64> {
 }
 
 pub fn number(input: &str) -> IResult<&str, u64> {
-    alt((prefixed_hex, suffixed_num))(input)
+    expected("number", alt((prefixed_hex, suffixed_num)))(input)
 }
 
 #[cfg(test)]
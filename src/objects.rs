@@ -0,0 +1,203 @@
+use crate::layout::InputSection;
+use object::read::archive::ArchiveFile;
+use object::{Object, ObjectKind, ObjectSection, SectionFlags, SectionKind};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ObjectError {
+    Io {
+        file: String,
+        error: io::Error,
+    },
+    /// The file is not an ELF relocatable object or an archive of them
+    Format {
+        file: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjectError::Io { file, error } => write!(f, "{}: {}", file, error),
+            ObjectError::Format { file, message } => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjectError::Io { error, .. } => Some(error),
+            ObjectError::Format { .. } => None,
+        }
+    }
+}
+
+fn format_error(file: &str) -> impl Fn(object::Error) -> ObjectError + '_ {
+    move |error| ObjectError::Format {
+        file: file.into(),
+        message: error.to_string(),
+    }
+}
+
+/// Reads the input sections of an ELF relocatable object, or of every
+/// object in an `ar` archive. Thin archives are rejected.
+pub fn read_input_sections<P: AsRef<Path>>(path: P) -> Result<Vec<InputSection>, ObjectError> {
+    let path = path.as_ref();
+    let file = path.display().to_string();
+    match fs::read(path) {
+        Ok(data) => parse_input_sections(&file, &data),
+        Err(error) => Err(ObjectError::Io { file, error }),
+    }
+}
+
+/// Reads the input sections of an object or archive that is already in
+/// memory, `file` is the name reported for it.
+pub fn parse_input_sections(file: &str, data: &[u8]) -> Result<Vec<InputSection>, ObjectError> {
+    if data.starts_with(&object::archive::THIN_MAGIC) {
+        // The members of a thin archive are separate files named by the
+        // archive, which can't be reached from its contents alone
+        return Err(ObjectError::Format {
+            file: file.into(),
+            message: "thin archives are not supported".into(),
+        });
+    }
+    if !data.starts_with(&object::archive::MAGIC) {
+        return object_sections(file, data);
+    }
+
+    let archive = ArchiveFile::parse(data).map_err(format_error(file))?;
+    let mut sections = Vec::new();
+    for member in archive.members() {
+        let member = member.map_err(format_error(file))?;
+        let name = String::from_utf8_lossy(member.name()).into_owned();
+        let qualified = format!("{}({})", file, name);
+        let member_data = member.data(data).map_err(format_error(&qualified))?;
+        let inputs = object_sections(&qualified, member_data)?;
+        sections.extend(inputs.into_iter().map(|input| InputSection {
+            file: name.clone(),
+            archive: Some(file.into()),
+            ..input
+        }));
+    }
+    Ok(sections)
+}
+
+fn object_sections(file: &str, data: &[u8]) -> Result<Vec<InputSection>, ObjectError> {
+    let object = object::File::parse(data).map_err(format_error(file))?;
+    if object.kind() != ObjectKind::Relocatable {
+        return Err(ObjectError::Format {
+            file: file.into(),
+            message: "not a relocatable object".into(),
+        });
+    }
+    object
+        .sections()
+        .filter(|section| section.kind() != SectionKind::Metadata)
+        .map(|section| {
+            let name = section.name().map_err(format_error(file))?;
+            let flags = match section.flags() {
+                SectionFlags::Elf { sh_flags } => sh_flags,
+                _ => 0,
+            };
+            Ok(InputSection {
+                file: file.into(),
+                archive: None,
+                name: name.into(),
+                size: section.size(),
+                align: section.align(),
+                flags,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::*;
+    use object::write;
+    use object::{Architecture, BinaryFormat, Endianness};
+
+    fn elf_object() -> Vec<u8> {
+        let mut object =
+            write::Object::new(BinaryFormat::Elf, Architecture::Arm, Endianness::Little);
+        let text = object.add_section(Vec::new(), b".text.main".to_vec(), SectionKind::Text);
+        object.append_section_data(text, &[0; 0x22], 4);
+        let bss = object.add_section(Vec::new(), b".bss".to_vec(), SectionKind::UninitializedData);
+        object.append_section_bss(bss, 0x100, 8);
+        object.write().unwrap()
+    }
+
+    fn archive(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut archive = b"!<arch>\n".to_vec();
+        for (name, data) in members {
+            let header = format!(
+                "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                format!("{}/", name),
+                0,
+                0,
+                0,
+                644,
+                data.len()
+            );
+            archive.extend(header.as_bytes());
+            archive.extend(*data);
+            if data.len() % 2 == 1 {
+                archive.push(b'\n');
+            }
+        }
+        archive
+    }
+
+    #[test]
+    fn test_object() {
+        let sections = parse_input_sections("main.o", &elf_object()).unwrap();
+        let sections: Vec<_> = sections
+            .iter()
+            .map(|s| (s.file.as_str(), s.name.as_str(), s.size, s.align, s.flags))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![
+                ("main.o", ".text.main", 0x22, 4, 0x6),
+                ("main.o", ".bss", 0x100, 8, 0x3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_archive() {
+        let object = elf_object();
+        let data = archive(&[("a.o", &object), ("b.o", &object)]);
+        let sections = parse_input_sections("libfoo.a", &data).unwrap();
+        assert_eq!(sections.len(), 4);
+        assert_eq!(sections[2].file, "b.o");
+        assert_eq!(sections[2].archive.as_deref(), Some("libfoo.a"));
+        assert_eq!(sections[2].name, ".text.main");
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = parse_input_sections("x.o", b"not an object").unwrap_err();
+        assert!(matches!(error, ObjectError::Format { file, .. } if file == "x.o"));
+
+        let data = archive(&[("bad.o", b"junk")]);
+        let error = parse_input_sections("libbad.a", &data).unwrap_err();
+        assert!(matches!(error, ObjectError::Format { file, .. } if file == "libbad.a(bad.o)"));
+
+        let mut data = archive(&[("a.o", b"")]);
+        data[..8].copy_from_slice(b"!<thin>\n");
+        let error = parse_input_sections("libthin.a", &data).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "libthin.a: thin archives are not supported"
+        );
+
+        let error = read_input_sections("does/not/exist.o").unwrap_err();
+        assert!(matches!(error, ObjectError::Io { .. }));
+    }
+}
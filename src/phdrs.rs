@@ -0,0 +1,183 @@
+use super::error::{tag, IResult};
+use super::expressions::expression;
+use super::expressions::Expression;
+use super::idents::{is_ident_char, symbol};
+use super::span::{spanned, SourceSpan};
+use nom::branch::alt;
+use nom::character::complete::satisfy;
+use nom::combinator::map;
+use nom::combinator::map_opt;
+use nom::combinator::not;
+use nom::multi::fold_many0;
+use nom::sequence::delimited;
+use nom::sequence::preceded;
+use nom::sequence::terminated;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProgramHeader {
+    pub name: String,
+    pub p_type: ProgramHeaderType,
+    pub filehdr: bool,
+    pub phdrs: bool,
+    pub at: Option<Box<Expression>>,
+    pub flags: Option<Box<Expression>>,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ProgramHeaderType {
+    Null,
+    Load,
+    Dynamic,
+    Interp,
+    Note,
+    Shlib,
+    Phdr,
+    Tls,
+    GnuEhFrame,
+    GnuStack,
+    GnuRelro,
+    GnuProperty,
+    Value(Box<Expression>),
+}
+
+enum Qualifier {
+    FileHdr,
+    Phdrs,
+    At(Expression),
+    Flags(Expression),
+}
+
+fn named_type(input: &str) -> IResult<&str, ProgramHeaderType> {
+    map_opt(symbol, |name: &str| match name {
+        "PT_NULL" => Some(ProgramHeaderType::Null),
+        "PT_LOAD" => Some(ProgramHeaderType::Load),
+        "PT_DYNAMIC" => Some(ProgramHeaderType::Dynamic),
+        "PT_INTERP" => Some(ProgramHeaderType::Interp),
+        "PT_NOTE" => Some(ProgramHeaderType::Note),
+        "PT_SHLIB" => Some(ProgramHeaderType::Shlib),
+        "PT_PHDR" => Some(ProgramHeaderType::Phdr),
+        "PT_TLS" => Some(ProgramHeaderType::Tls),
+        "PT_GNU_EH_FRAME" => Some(ProgramHeaderType::GnuEhFrame),
+        "PT_GNU_STACK" => Some(ProgramHeaderType::GnuStack),
+        "PT_GNU_RELRO" => Some(ProgramHeaderType::GnuRelro),
+        "PT_GNU_PROPERTY" => Some(ProgramHeaderType::GnuProperty),
+        _ => None,
+    })(input)
+}
+
+fn header_type(input: &str) -> IResult<&str, ProgramHeaderType> {
+    alt((
+        named_type,
+        map(expression, |expr| ProgramHeaderType::Value(Box::new(expr))),
+    ))(input)
+}
+
+/// Matches `word` only if it is not the start of a longer name
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(tag(word), not(satisfy(is_ident_char)))
+}
+
+fn qualifier(input: &str) -> IResult<&str, Qualifier> {
+    alt((
+        map(keyword("FILEHDR"), |_| Qualifier::FileHdr),
+        map(keyword("PHDRS"), |_| Qualifier::Phdrs),
+        map(
+            preceded(
+                tag("AT"),
+                delimited(wsc!(tag("(")), expression, wsc!(tag(")"))),
+            ),
+            Qualifier::At,
+        ),
+        map(
+            preceded(
+                tag("FLAGS"),
+                delimited(wsc!(tag("(")), expression, wsc!(tag(")"))),
+            ),
+            Qualifier::Flags,
+        ),
+    ))(input)
+}
+
+pub fn program_header(input: &str) -> IResult<&str, ProgramHeader> {
+    spanned(program_header_inner)(input)
+}
+
+fn program_header_inner(input: &str) -> IResult<&str, ProgramHeader> {
+    let (input, name) = symbol(input)?;
+    let (input, p_type) = wsc!(header_type)(input)?;
+    let (input, header) = fold_many0(
+        wsc!(qualifier),
+        || ProgramHeader {
+            name: name.into(),
+            p_type: p_type.clone(),
+            filehdr: false,
+            phdrs: false,
+            at: None,
+            flags: None,
+            span: SourceSpan::default(),
+        },
+        |mut header, qualifier| {
+            match qualifier {
+                Qualifier::FileHdr => header.filehdr = true,
+                Qualifier::Phdrs => header.phdrs = true,
+                Qualifier::At(expr) => header.at = Some(Box::new(expr)),
+                Qualifier::Flags(expr) => header.flags = Some(Box::new(expr)),
+            }
+            header
+        },
+    )(input)?;
+    let (input, _) = tag(";")(input)?;
+    Ok((input, header))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::expressions::Expression;
+    use crate::phdrs::*;
+
+    #[test]
+    fn test_program_header() {
+        assert_done!(
+            program_header("text PT_LOAD FILEHDR PHDRS FLAGS(5);"),
+            ProgramHeader {
+                name: "text".into(),
+                p_type: ProgramHeaderType::Load,
+                filehdr: true,
+                phdrs: true,
+                at: None,
+                flags: Some(Box::new(Expression::Number(5))),
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            program_header("headers PT_PHDR PHDRS ;"),
+            ProgramHeader {
+                name: "headers".into(),
+                p_type: ProgramHeaderType::Phdr,
+                filehdr: false,
+                phdrs: true,
+                at: None,
+                flags: None,
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            program_header("custom 0x6474e550 AT ( 0x1000 ) ;"),
+            ProgramHeader {
+                name: "custom".into(),
+                p_type: ProgramHeaderType::Value(Box::new(Expression::Number(0x6474e550))),
+                filehdr: false,
+                phdrs: false,
+                at: Some(Box::new(Expression::Number(0x1000))),
+                flags: None,
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(program_header("stack PT_GNU_STACK FLAGS ( 6 ) ;"));
+
+        assert_fail!(program_header("text PT_LOAD"));
+        assert_fail!(program_header("text PT_LOAD PHDRSEG;"));
+        assert_fail!(program_header("text PT_LOAD FILEHDR_X;"));
+    }
+}
@@ -1,23 +1,41 @@
 use super::commands::{command, Command};
-use super::memory::region;
-use super::memory::Region;
+use super::error::{context, tag, IResult};
+use super::memory::memory_command;
+use super::memory::MemoryCommand;
+use super::phdrs::{program_header, ProgramHeader};
 use super::sections::section_command;
 use super::sections::SectionCommand;
+use super::span::{spanned, SourceSpan};
 use super::statements::{statement, Statement};
+use super::version::{version_command, VersionNode};
 use super::whitespace::opt_space;
 use nom::branch::alt;
-use nom::bytes::complete::tag;
+use nom::combinator::cut;
 use nom::combinator::map;
+use nom::multi::many0;
 use nom::multi::many1;
 use nom::sequence::tuple;
-use nom::IResult;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum RootItem {
     Statement(Statement),
     Command(Command),
-    Memory { regions: Vec<Region> },
-    Sections { list: Vec<SectionCommand> },
+    Memory {
+        commands: Vec<MemoryCommand>,
+        span: SourceSpan,
+    },
+    Sections {
+        list: Vec<SectionCommand>,
+        span: SourceSpan,
+    },
+    Phdrs {
+        headers: Vec<ProgramHeader>,
+        span: SourceSpan,
+    },
+    Version {
+        nodes: Vec<VersionNode>,
+        span: SourceSpan,
+    },
 }
 
 fn statement_item(input: &str) -> IResult<&str, RootItem> {
@@ -30,24 +48,90 @@ fn command_item(input: &str) -> IResult<&str, RootItem> {
 
 fn memory_item(input: &str) -> IResult<&str, RootItem> {
     let (input, _) = tuple((tag("MEMORY"), wsc!(tag("{"))))(input)?;
-    let (input, regions) = many1(wsc!(region))(input)?;
-    let (input, _) = tag("}")(input)?;
-    Ok((input, RootItem::Memory { regions }))
+    context(
+        || "MEMORY".into(),
+        cut(|input| {
+            let (input, commands) = many1(wsc!(memory_command))(input)?;
+            let (input, _) = tag("}")(input)?;
+            Ok((
+                input,
+                RootItem::Memory {
+                    commands,
+                    span: SourceSpan::default(),
+                },
+            ))
+        }),
+    )(input)
 }
 
 fn sections_item(input: &str) -> IResult<&str, RootItem> {
     let (input, _) = tuple((tag("SECTIONS"), wsc!(tag("{"))))(input)?;
-    let (input, sections) = many1(wsc!(section_command))(input)?;
-    let (input, _) = tag("}")(input)?;
-    Ok((input, RootItem::Sections { list: sections }))
+    context(
+        || "SECTIONS".into(),
+        cut(|input| {
+            let (input, sections) = many1(wsc!(section_command))(input)?;
+            let (input, _) = tag("}")(input)?;
+            Ok((
+                input,
+                RootItem::Sections {
+                    list: sections,
+                    span: SourceSpan::default(),
+                },
+            ))
+        }),
+    )(input)
+}
+
+fn phdrs_item(input: &str) -> IResult<&str, RootItem> {
+    let (input, _) = tuple((tag("PHDRS"), wsc!(tag("{"))))(input)?;
+    context(
+        || "PHDRS".into(),
+        cut(|input| {
+            let (input, headers) = many0(wsc!(program_header))(input)?;
+            let (input, _) = tag("}")(input)?;
+            Ok((
+                input,
+                RootItem::Phdrs {
+                    headers,
+                    span: SourceSpan::default(),
+                },
+            ))
+        }),
+    )(input)
+}
+
+fn version_item(input: &str) -> IResult<&str, RootItem> {
+    map(version_command, |nodes| RootItem::Version {
+        nodes,
+        span: SourceSpan::default(),
+    })(input)
 }
 
 fn root_item(input: &str) -> IResult<&str, RootItem> {
-    alt((statement_item, memory_item, sections_item, command_item))(input)
+    spanned(alt((
+        statement_item,
+        memory_item,
+        sections_item,
+        phdrs_item,
+        version_item,
+        command_item,
+    )))(input)
 }
 
 pub fn parse(input: &str) -> IResult<&str, Vec<RootItem>> {
-    alt((many1(wsc!(root_item)), map(opt_space, |_| vec![])))(input)
+    parse_items(root_item, input)
+}
+
+/// Parses a whole file made of `item`s, such as a script or a file included
+/// inside a block.
+pub fn parse_items<O>(item: fn(&str) -> IResult<&str, O>, input: &str) -> IResult<&str, Vec<O>> {
+    let (input, items) = many0(wsc!(item))(input)?;
+    let (input, _) = opt_space(input)?;
+    if !input.is_empty() {
+        // Parse the leftover again to report why it isn't a valid item
+        item(input)?;
+    }
+    Ok((input, items))
 }
 
 #[cfg(test)]
@@ -63,6 +147,13 @@ mod tests {
         assert_done_vec!(parse("      /* hello */              "), 0);
     }
 
+    #[test]
+    fn test_leftover() {
+        assert_fail!(parse("ENTRY(_start) }"));
+        assert_fail!(parse("SECTIONS { .text : { *(.text) } "));
+        assert_fail!(parse("MEMORY { ROM : ORIGIN = 0, LENGTH = 1K } junk"));
+    }
+
     #[test]
     fn test_parse() {
         for entry in fs::read_dir("tests").unwrap() {
@@ -1,27 +1,41 @@
-use super::commands::{command, Command};
+use super::commands::{command, include_file, Command};
+use super::error::{context, tag, IResult};
 use super::expressions::expression;
 use super::expressions::Expression;
+use super::idents::is_ident_char;
+use super::idents::is_pattern;
 use super::idents::pattern;
+use super::idents::string;
 use super::idents::symbol;
+use super::span::{spanned, SourceSpan};
 use super::statements::{statement, Statement};
-use super::whitespace::opt_space;
+use super::whitespace::{opt_space, space};
 use nom::branch::alt;
-use nom::bytes::complete::tag;
+use nom::character::complete::{hex_digit1, satisfy};
+use nom::combinator::consumed;
 use nom::combinator::cut;
 use nom::combinator::map;
+use nom::combinator::map_opt;
+use nom::combinator::not;
 use nom::combinator::opt;
+use nom::combinator::peek;
+use nom::combinator::recognize;
+use nom::combinator::verify;
 use nom::multi::many0;
 use nom::multi::many1;
+use nom::multi::separated_list1;
 use nom::sequence::delimited;
+use nom::sequence::pair;
 use nom::sequence::preceded;
+use nom::sequence::terminated;
 use nom::sequence::tuple;
-use nom::IResult;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum SectionCommand {
     Statement(Statement),
     Command(Command),
     OutputSection(OutputSection),
+    Overlay(Overlay),
 }
 
 #[derive(Default, Debug, PartialEq, Clone)]
@@ -37,7 +51,37 @@ pub struct OutputSection {
     pub content: Vec<OutputSectionCommand>,
     pub region: Option<String>,
     pub lma_region: Option<String>,
+    pub phdrs: Vec<String>,
     pub fillexp: Option<Box<Expression>>,
+    /// `=0x...` with a bare hex number, set instead of `fillexp`
+    pub fill_pattern: Option<Vec<u8>>,
+    pub span: SourceSpan,
+}
+
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct Overlay {
+    pub start: Option<Box<Expression>>,
+    pub nocrossrefs: bool,
+    pub lma_address: Option<Box<Expression>>,
+    pub sections: Vec<OverlaySection>,
+    pub region: Option<String>,
+    pub lma_region: Option<String>,
+    pub phdrs: Vec<String>,
+    pub fillexp: Option<Box<Expression>>,
+    /// `=0x...` with a bare hex number, set instead of `fillexp`
+    pub fill_pattern: Option<Vec<u8>>,
+    pub span: SourceSpan,
+}
+
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct OverlaySection {
+    pub name: String,
+    pub content: Vec<OutputSectionCommand>,
+    pub phdrs: Vec<String>,
+    pub fillexp: Option<Box<Expression>>,
+    /// `=0x...` with a bare hex number, set instead of `fillexp`
+    pub fill_pattern: Option<Vec<u8>>,
+    pub span: SourceSpan,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -45,36 +89,84 @@ pub enum OutputSectionCommand {
     Statement(Statement),
     Fill {
         expr: Box<Expression>,
+        span: SourceSpan,
+    },
+    /// `FILL` with a bare hex number, a byte pattern whose length includes
+    /// leading zeros, e.g. `FILL(0x0090)` or `FILL(0x0102030405060708090a)`
+    FillPattern {
+        pattern: Vec<u8>,
+        span: SourceSpan,
     },
     Data {
         d_type: DataType,
         value: Box<Expression>,
+        span: SourceSpan,
     },
     InputSection {
+        flags: Option<InputSectionFlags>,
         file: SectionPattern,
         sections: Vec<SectionPattern>,
+        span: SourceSpan,
     },
     KeepInputSection {
+        flags: Option<InputSectionFlags>,
         file: SectionPattern,
         sections: Vec<SectionPattern>,
+        span: SourceSpan,
+    },
+    Include {
+        file: String,
+        span: SourceSpan,
+    },
+    /// Zero terminated string, written as `ASCIZ "string"`
+    Asciz {
+        string: String,
+        span: SourceSpan,
+    },
+    /// Version string of the linker
+    LinkerVersion {
+        span: SourceSpan,
+    },
+    /// `CONSTRUCTORS`, or `SORT(CONSTRUCTORS)` when `sorted`
+    Constructors {
+        sorted: bool,
+        span: SourceSpan,
+    },
+    CreateObjectSymbols {
+        span: SourceSpan,
     },
 }
 
+/// Section flags an input section must have (`SHF_WRITE`) and must not have
+/// (`!SHF_EXECINSTR`) to match an input section description, as given to
+/// `INPUT_SECTION_FLAGS`.
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct InputSectionFlags {
+    pub required: Vec<String>,
+    pub excluded: Vec<String>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum DataType {
     Byte,
     Short,
     Long,
     Quad,
+    SQuad,
 }
 
+/// File or section pattern of an input section description. Sorting
+/// wrappers nest, e.g. `SORT_BY_NAME(SORT_BY_ALIGNMENT(.text.*))` sorts by
+/// name and then by alignment.
 #[derive(Debug, PartialEq, Clone)]
 pub enum SectionPattern {
     Simple(String),
-    SortByName(String),
-    SortByAlignment(String),
-    SortByInitPriority(String),
-    SortNone(String),
+    SortByName(Box<SectionPattern>),
+    SortByAlignment(Box<SectionPattern>),
+    SortByInitPriority(Box<SectionPattern>),
+    SortNone(Box<SectionPattern>),
+    /// Reverses the sorting it wraps, or sorts by name in reverse on its own
+    Reverse(Box<SectionPattern>),
     ExcludeFile {
         files: Vec<String>,
         pattern: Box<SectionPattern>,
@@ -88,6 +180,12 @@ pub enum OutputSectionType {
     Copy,
     Info,
     Overlay,
+    ReadOnly,
+    /// ELF section type, e.g. `(TYPE = SHT_PROGBITS)` or `(TYPE = 0x70000001)`
+    Type(Box<Expression>),
+    /// ELF section type of a read-only section, e.g.
+    /// `(READONLY (TYPE = SHT_PROGBITS))`
+    ReadOnlyType(Box<Expression>),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -96,16 +194,39 @@ pub enum OutputSectionConstraint {
     OnlyIfRw,
 }
 
-fn output_section_type(input: &str) -> IResult<&str, OutputSectionType> {
+fn section_type_keyword(input: &str) -> IResult<&str, OutputSectionType> {
     alt((
-        map(tag("(NOLOAD)"), |_| OutputSectionType::NoLoad),
-        map(tag("(DSECT)"), |_| OutputSectionType::DSect),
-        map(tag("(COPY)"), |_| OutputSectionType::Copy),
-        map(tag("(INFO)"), |_| OutputSectionType::Info),
-        map(tag("(OVERLAY)"), |_| OutputSectionType::Overlay),
+        map(tag("NOLOAD"), |_| OutputSectionType::NoLoad),
+        map(tag("DSECT"), |_| OutputSectionType::DSect),
+        map(tag("COPY"), |_| OutputSectionType::Copy),
+        map(tag("INFO"), |_| OutputSectionType::Info),
+        map(tag("OVERLAY"), |_| OutputSectionType::Overlay),
+        map(
+            preceded(
+                pair(tag("READONLY"), wsc!(tag("("))),
+                terminated(elf_section_type, pair(opt_space, tag(")"))),
+            ),
+            OutputSectionType::ReadOnlyType,
+        ),
+        map(tag("READONLY"), |_| OutputSectionType::ReadOnly),
+        map(elf_section_type, OutputSectionType::Type),
     ))(input)
 }
 
+fn elf_section_type(input: &str) -> IResult<&str, Box<Expression>> {
+    let (input, _) = pair(tag("TYPE"), wsc!(tag("=")))(input)?;
+    let (input, expr) = expression(input)?;
+    Ok((input, Box::new(expr)))
+}
+
+fn output_section_type(input: &str) -> IResult<&str, OutputSectionType> {
+    delimited(
+        wsc!(tag("(")),
+        section_type_keyword,
+        pair(opt_space, tag(")")),
+    )(input)
+}
+
 fn output_section_constraint(input: &str) -> IResult<&str, OutputSectionConstraint> {
     alt((
         map(tag("ONLY_IF_RO"), |_| OutputSectionConstraint::OnlyIfRo),
@@ -122,21 +243,29 @@ fn sorted_sp(input: &str) -> IResult<&str, SectionPattern> {
         tag("SORT"),
     ))(input)?;
     let (input, _) = cut(wsc!(tag("(")))(input)?;
-    let (input, inner) = cut(pattern)(input)?;
+    let (input, inner) = cut(section_pattern)(input)?;
     let (input, _) = cut(opt_space)(input)?;
     let (input, _) = cut(tag(")"))(input)?;
+    let inner = Box::new(inner);
     Ok((
         input,
         match keyword {
-            "SORT" | "SORT_BY_NAME" => SectionPattern::SortByName(inner.into()),
-            "SORT_BY_ALIGNMENT" => SectionPattern::SortByAlignment(inner.into()),
-            "SORT_BY_INIT_PRIORITY" => SectionPattern::SortByInitPriority(inner.into()),
-            "SORT_NONE" => SectionPattern::SortNone(inner.into()),
+            "SORT" | "SORT_BY_NAME" => SectionPattern::SortByName(inner),
+            "SORT_BY_ALIGNMENT" => SectionPattern::SortByAlignment(inner),
+            "SORT_BY_INIT_PRIORITY" => SectionPattern::SortByInitPriority(inner),
+            "SORT_NONE" => SectionPattern::SortNone(inner),
             _ => panic!("wrong sort keyword"),
         },
     ))
 }
 
+fn reverse_sp(input: &str) -> IResult<&str, SectionPattern> {
+    let (input, _) = tuple((tag("REVERSE"), wsc!(tag("("))))(input)?;
+    let (input, inner) = cut(section_pattern)(input)?;
+    let (input, _) = cut(wsc!(tag(")")))(input)?;
+    Ok((input, SectionPattern::Reverse(Box::new(inner))))
+}
+
 fn exclude_file_sp(input: &str) -> IResult<&str, SectionPattern> {
     let (input, _) = tuple((tag("EXCLUDE_FILE"), opt_space, tag("(")))(input)?;
     let (input, files) = cut(many1(wsc!(map(pattern, String::from))))(input)?;
@@ -156,11 +285,17 @@ fn simple_sp(input: &str) -> IResult<&str, SectionPattern> {
 }
 
 fn section_pattern(input: &str) -> IResult<&str, SectionPattern> {
-    alt((exclude_file_sp, sorted_sp, simple_sp))(input)
+    alt((exclude_file_sp, sorted_sp, reverse_sp, simple_sp))(input)
 }
 
 fn data_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
-    let (input, d_type) = alt((tag("BYTE"), tag("SHORT"), tag("LONG"), tag("QUAD")))(input)?;
+    let (input, d_type) = alt((
+        tag("BYTE"),
+        tag("SHORT"),
+        tag("LONG"),
+        tag("QUAD"),
+        tag("SQUAD"),
+    ))(input)?;
     let (input, _) = wsc!(tag("("))(input)?;
     let (input, value) = expression(input)?;
     let (input, _) = tuple((wsc!(tag(")")), opt(tag(";"))))(input)?;
@@ -172,30 +307,171 @@ fn data_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
                 "SHORT" => DataType::Short,
                 "LONG" => DataType::Long,
                 "QUAD" => DataType::Quad,
+                "SQUAD" => DataType::SQuad,
                 _ => panic!("invalid data type"),
             },
             value: Box::new(value),
+            span: SourceSpan::default(),
         },
     ))
 }
 
+/// A `FILL` argument or `=fillexp`
+enum FillValue {
+    Expression(Expression),
+    /// A bare hex number, kept as bytes so that leading zeros set the
+    /// pattern length
+    Pattern(Vec<u8>),
+}
+
+/// Bytes of a bare hex number, e.g. `0x0090` is `[0x00, 0x90]`
+fn hex_pattern(text: &str) -> Option<Vec<u8>> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    // An odd number of digits is padded on the left
+    let digits = format!("{:0>1$}", digits, digits.len() + digits.len() % 2);
+    Some(
+        (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+            .collect(),
+    )
+}
+
+fn fill_value(input: &str) -> IResult<&str, FillValue> {
+    alt((
+        // Patterns too long for a number
+        map_opt(
+            recognize(terminated(
+                pair(
+                    alt((tag("0x"), tag("0X"))),
+                    verify(hex_digit1, |digits: &str| digits.len() > 16),
+                ),
+                not(satisfy(is_ident_char)),
+            )),
+            |text| hex_pattern(text).map(FillValue::Pattern),
+        ),
+        map(consumed(expression), |(text, expr)| {
+            match hex_pattern(text.trim_end()) {
+                Some(pattern) => FillValue::Pattern(pattern),
+                None => FillValue::Expression(expr),
+            }
+        }),
+    ))(input)
+}
+
 fn fill_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
     let (input, _) = tuple((tag("FILL"), wsc!(tag("("))))(input)?;
-    let (input, expr) = expression(input)?;
+    let (input, value) = fill_value(input)?;
     let (input, _) = tuple((wsc!(tag(")")), opt(tag(";"))))(input)?;
     Ok((
         input,
-        OutputSectionCommand::Fill {
-            expr: Box::new(expr),
+        match value {
+            FillValue::Expression(expr) => OutputSectionCommand::Fill {
+                expr: Box::new(expr),
+                span: SourceSpan::default(),
+            },
+            FillValue::Pattern(pattern) => OutputSectionCommand::FillPattern {
+                pattern,
+                span: SourceSpan::default(),
+            },
         },
     ))
 }
 
+fn asciz_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
+    // The keyword must not be the start of a file pattern
+    let (input, _) = tuple((tag("ASCIZ"), not(satisfy(is_pattern)), opt_space))(input)?;
+    let (input, text) = cut(string)(input)?;
+    let (input, _) = pair(opt_space, opt(tag(";")))(input)?;
+    Ok((
+        input,
+        OutputSectionCommand::Asciz {
+            string: text.into(),
+            span: SourceSpan::default(),
+        },
+    ))
+}
+
+fn keyword_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
+    let (input, command) = alt((
+        terminated(
+            alt((
+                map(tag("LINKER_VERSION"), |_| {
+                    OutputSectionCommand::LinkerVersion {
+                        span: SourceSpan::default(),
+                    }
+                }),
+                map(tag("CONSTRUCTORS"), |_| {
+                    OutputSectionCommand::Constructors {
+                        sorted: false,
+                        span: SourceSpan::default(),
+                    }
+                }),
+                map(tag("CREATE_OBJECT_SYMBOLS"), |_| {
+                    OutputSectionCommand::CreateObjectSymbols {
+                        span: SourceSpan::default(),
+                    }
+                }),
+            )),
+            // The keyword must not be the start of a file pattern
+            not(satisfy(is_pattern)),
+        ),
+        map(
+            tuple((
+                alt((tag("SORT_BY_NAME"), tag("SORT"))),
+                wsc!(tag("(")),
+                tag("CONSTRUCTORS"),
+                opt_space,
+                tag(")"),
+            )),
+            |_| OutputSectionCommand::Constructors {
+                sorted: true,
+                span: SourceSpan::default(),
+            },
+        ),
+    ))(input)?;
+    let (input, _) = pair(opt_space, opt(tag(";")))(input)?;
+    Ok((input, command))
+}
+
+fn include_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
+    map(include_file, |file| OutputSectionCommand::Include {
+        file: file.into(),
+        span: SourceSpan::default(),
+    })(input)
+}
+
 fn statement_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
     map(statement, OutputSectionCommand::Statement)(input)
 }
 
+/// Parses the flags of `INPUT_SECTION_FLAGS`, without its parentheses
+pub(crate) fn input_section_flag_list(input: &str) -> IResult<&str, InputSectionFlags> {
+    let (input, flags) = separated_list1(wsc!(tag("&")), pair(opt(wsc!(tag("!"))), symbol))(input)?;
+    let mut result = InputSectionFlags::default();
+    for (negated, flag) in flags {
+        match negated {
+            Some(_) => result.excluded.push(flag.into()),
+            None => result.required.push(flag.into()),
+        }
+    }
+    Ok((input, result))
+}
+
+fn input_section_flags(input: &str) -> IResult<&str, InputSectionFlags> {
+    let (input, _) = tuple((tag("INPUT_SECTION_FLAGS"), wsc!(tag("("))))(input)?;
+    let (input, flags) = cut(input_section_flag_list)(input)?;
+    let (input, _) = cut(wsc!(tag(")")))(input)?;
+    Ok((input, flags))
+}
+
 fn input_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
+    let (input, flags) = opt(input_section_flags)(input)?;
     let (input, file) = section_pattern(input)?;
     let (input, _) = opt_space(input)?;
     let (input, sections) = opt(delimited(
@@ -206,8 +482,10 @@ fn input_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
     Ok((
         input,
         OutputSectionCommand::InputSection {
+            flags,
             file,
             sections: sections.unwrap_or_default(),
+            span: SourceSpan::default(),
         },
     ))
 }
@@ -219,16 +497,33 @@ fn keep_osc(input: &str) -> IResult<&str, OutputSectionCommand> {
     Ok((
         input,
         match inner {
-            OutputSectionCommand::InputSection { file, sections } => {
-                OutputSectionCommand::KeepInputSection { file, sections }
-            }
+            OutputSectionCommand::InputSection {
+                flags,
+                file,
+                sections,
+                span,
+            } => OutputSectionCommand::KeepInputSection {
+                flags,
+                file,
+                sections,
+                span,
+            },
             _ => panic!("wrong output section command"),
         },
     ))
 }
 
-fn output_section_command(input: &str) -> IResult<&str, OutputSectionCommand> {
-    alt((statement_osc, keep_osc, data_osc, fill_osc, input_osc))(input)
+pub fn output_section_command(input: &str) -> IResult<&str, OutputSectionCommand> {
+    spanned(alt((
+        statement_osc,
+        keep_osc,
+        data_osc,
+        fill_osc,
+        asciz_osc,
+        include_osc,
+        keyword_osc,
+        input_osc,
+    )))(input)
 }
 
 fn statement_sc(input: &str) -> IResult<&str, SectionCommand> {
@@ -242,45 +537,163 @@ fn command_sc(input: &str) -> IResult<&str, SectionCommand> {
 fn output_sc(input: &str) -> IResult<&str, SectionCommand> {
     let (input, name) = alt((tag("/DISCARD/"), symbol))(input)?;
     let (input, _) = opt_space(input)?;
+    // A single type, before or after the address
     let (input, s_type1) = opt(output_section_type)(input)?;
-    let (input, vma) = wsc!(opt(expression))(input)?;
-    let (input, s_type2) = opt(output_section_type)(input)?;
+    let (input, vma) = wsc!(opt(preceded(not(output_section_type), expression)))(input)?;
+    let (input, s_type2) = match s_type1 {
+        Some(_) => (input, None),
+        None => opt(output_section_type)(input)?,
+    };
     let (input, _) = wsc!(tag(":"))(input)?;
-    let (input, lma) = opt(delimited(tag("AT("), wsc!(expression), tag(")")))(input)?;
+    let (input, section) = context(
+        move || format!("output section `{}`", name),
+        cut(output_section_body),
+    )(input)?;
+    Ok((
+        input,
+        SectionCommand::OutputSection(OutputSection {
+            name: name.into(),
+            vma_address: vma.map(Box::new),
+            s_type: s_type1.or(s_type2),
+            ..section
+        }),
+    ))
+}
+
+/// Parses `keyword(expression)`, allowing spaces before the parenthesis
+fn parenthesized<'a>(keyword: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, Expression> {
+    delimited(
+        pair(tag(keyword), wsc!(tag("("))),
+        expression,
+        pair(opt_space, tag(")")),
+    )
+}
+
+/// Parses the `>region AT>lma_region` clauses following the closing brace of
+/// an output section or overlay
+fn region_clauses(input: &str) -> IResult<&str, (Option<String>, Option<String>)> {
+    let (input, region) = opt(preceded(tag(">"), wsc!(symbol)))(input)?;
+    let (input, lma_region) = opt(preceded(
+        tuple((tag("AT"), opt_space, tag(">"))),
+        wsc!(symbol),
+    ))(input)?;
+    Ok((
+        input,
+        (region.map(String::from), lma_region.map(String::from)),
+    ))
+}
+
+/// Phdrs and fill of an output section, overlay or overlay section
+type PhdrsFill = (Vec<String>, Option<Box<Expression>>, Option<Vec<u8>>);
+
+/// Parses the `:phdr =fillexp ,` clauses ending an output section, overlay or
+/// overlay section
+fn phdrs_fill_clauses(input: &str) -> IResult<&str, PhdrsFill> {
+    let (input, phdrs) = many0(preceded(tag(":"), wsc!(symbol)))(input)?;
+    let (input, fill) = opt(preceded(tag("="), wsc!(fill_value)))(input)?;
+    let (input, _) = opt(tag(","))(input)?;
+    let phdrs = phdrs.into_iter().map(String::from).collect();
+    Ok((
+        input,
+        match fill {
+            Some(FillValue::Expression(expr)) => (phdrs, Some(Box::new(expr)), None),
+            Some(FillValue::Pattern(pattern)) => (phdrs, None, Some(pattern)),
+            None => (phdrs, None, None),
+        },
+    ))
+}
+
+fn output_section_body(input: &str) -> IResult<&str, OutputSection> {
+    let (input, lma) = opt(parenthesized("AT"))(input)?;
     let (input, _) = opt_space(input)?;
-    let (input, section_align) = opt(delimited(tag("ALIGN("), wsc!(expression), tag(")")))(input)?;
+    let (input, section_align) = opt(parenthesized("ALIGN"))(input)?;
     let (input, align_with_input) = wsc!(opt(tag("ALIGN_WITH_INPUT")))(input)?;
-    let (input, subsection_align) =
-        opt(delimited(tag("SUBALIGN("), wsc!(expression), tag(")")))(input)?;
+    let (input, subsection_align) = opt(parenthesized("SUBALIGN"))(input)?;
     let (input, constraint) = wsc!(opt(output_section_constraint))(input)?;
     let (input, _) = wsc!(tag("{"))(input)?;
     let (input, content) = many0(wsc!(output_section_command))(input)?;
     let (input, _) = wsc!(tag("}"))(input)?;
-    let (input, region) = opt(preceded(tag(">"), wsc!(symbol)))(input)?;
-    let (input, lma_region) = opt(preceded(tag("AT>"), wsc!(symbol)))(input)?;
-    let (input, fillexp) = opt(preceded(tag("="), wsc!(expression)))(input)?;
-    let (input, _) = opt(tag(","))(input)?;
+    let (input, (region, lma_region)) = region_clauses(input)?;
+    let (input, (phdrs, fillexp, fill_pattern)) = phdrs_fill_clauses(input)?;
     Ok((
         input,
-        SectionCommand::OutputSection(OutputSection {
-            name: name.into(),
-            vma_address: vma.map(Box::new),
-            s_type: if s_type1.is_some() { s_type1 } else { s_type2 },
+        OutputSection {
             lma_address: lma.map(Box::new),
             section_align: section_align.map(Box::new),
             align_with_input: align_with_input.is_some(),
             subsection_align: subsection_align.map(Box::new),
             constraint,
             content,
-            region: region.map(String::from),
-            lma_region: lma_region.map(String::from),
-            fillexp: fillexp.map(Box::new),
+            region,
+            lma_region,
+            phdrs,
+            fillexp,
+            fill_pattern,
+            ..Default::default()
+        },
+    ))
+}
+
+fn overlay_section(input: &str) -> IResult<&str, OverlaySection> {
+    let (input, name) = symbol(input)?;
+    let (input, _) = wsc!(tag("{"))(input)?;
+    context(
+        move || format!("overlay section `{}`", name),
+        cut(move |input| overlay_section_body(input, name)),
+    )(input)
+}
+
+fn overlay_section_body<'a>(input: &'a str, name: &str) -> IResult<&'a str, OverlaySection> {
+    let (input, content) = many0(wsc!(output_section_command))(input)?;
+    let (input, _) = wsc!(tag("}"))(input)?;
+    let (input, (phdrs, fillexp, fill_pattern)) = phdrs_fill_clauses(input)?;
+    Ok((
+        input,
+        OverlaySection {
+            name: name.into(),
+            content,
+            phdrs,
+            fillexp,
+            fill_pattern,
+            span: SourceSpan::default(),
+        },
+    ))
+}
+
+fn overlay_sc(input: &str) -> IResult<&str, SectionCommand> {
+    let (input, _) = tuple((tag("OVERLAY"), peek(alt((space, tag(":"))))))(input)?;
+    context(|| "OVERLAY".into(), cut(overlay_body))(input)
+}
+
+fn overlay_body(input: &str) -> IResult<&str, SectionCommand> {
+    let (input, start) = wsc!(opt(expression))(input)?;
+    let (input, _) = wsc!(tag(":"))(input)?;
+    let (input, nocrossrefs) = wsc!(opt(tag("NOCROSSREFS")))(input)?;
+    let (input, lma) = opt(parenthesized("AT"))(input)?;
+    let (input, _) = wsc!(tag("{"))(input)?;
+    let (input, sections) = many0(wsc!(spanned(overlay_section)))(input)?;
+    let (input, _) = wsc!(tag("}"))(input)?;
+    let (input, (region, lma_region)) = region_clauses(input)?;
+    let (input, (phdrs, fillexp, fill_pattern)) = phdrs_fill_clauses(input)?;
+    Ok((
+        input,
+        SectionCommand::Overlay(Overlay {
+            start: start.map(Box::new),
+            nocrossrefs: nocrossrefs.is_some(),
+            lma_address: lma.map(Box::new),
+            sections,
+            region,
+            lma_region,
+            phdrs,
+            fillexp,
+            fill_pattern,
+            span: SourceSpan::default(),
         }),
     ))
 }
 
 pub fn section_command(input: &str) -> IResult<&str, SectionCommand> {
-    alt((statement_sc, output_sc, command_sc))(input)
+    spanned(alt((statement_sc, overlay_sc, output_sc, command_sc)))(input)
 }
 
 #[cfg(test)]
@@ -301,6 +714,37 @@ mod tests {
             "EXCLUDE_FILE ( *a ) *b ( .c EXCLUDE_FILE ( *a ) .d )",
         ));
 
+        assert_done!(
+            section_pattern("SORT_BY_NAME(SORT_BY_ALIGNMENT(.text.*))"),
+            SectionPattern::SortByName(Box::new(SectionPattern::SortByAlignment(Box::new(
+                SectionPattern::Simple(".text.*".into())
+            ))))
+        );
+        assert_done!(section_pattern(
+            "SORT_BY_ALIGNMENT ( SORT_BY_NAME ( .data.* ) )"
+        ));
+        assert_done!(
+            section_pattern("SORT(EXCLUDE_FILE(*crtend.o) .ctors.*)"),
+            SectionPattern::SortByName(Box::new(SectionPattern::ExcludeFile {
+                files: vec!["*crtend.o".into()],
+                pattern: Box::new(SectionPattern::Simple(".ctors.*".into())),
+            }))
+        );
+        assert_done!(
+            section_pattern("REVERSE(SORT_BY_NAME(.text.*))"),
+            SectionPattern::Reverse(Box::new(SectionPattern::SortByName(Box::new(
+                SectionPattern::Simple(".text.*".into())
+            ))))
+        );
+        assert_done!(section_pattern(
+            "SORT_BY_INIT_PRIORITY(EXCLUDE_FILE(*crtbegin.o) .init_array.*)"
+        ));
+        assert_done!(
+            section_pattern("REVERSE"),
+            SectionPattern::Simple("REVERSE".into())
+        );
+        assert_fail!(section_pattern("SORT_BY_NAME(SORT_BY_ALIGNMENT(.text.*)"));
+
         assert_done!(output_section_command("[A-Z]*(.data)"));
         assert_done!(output_section_command(
             "LONG((__CTOR_END__ - __CTOR_LIST__) / 4 - 2)",
@@ -317,7 +761,72 @@ mod tests {
         assert_done!(output_section_command("KEEP(SORT_BY_NAME(*)(.ctors))"));
         assert_done!(output_section_command("PROVIDE (__init_array_end = .);"));
         assert_done!(output_section_command("LONG(0);"));
-        assert_done!(output_section_command("SORT(CONSTRUCTORS)"));
+        assert_done!(
+            output_section_command("SORT(CONSTRUCTORS)"),
+            OutputSectionCommand::Constructors {
+                sorted: true,
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            output_section_command("CONSTRUCTORS"),
+            OutputSectionCommand::Constructors {
+                sorted: false,
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(output_section_command("CREATE_OBJECT_SYMBOLS"));
+        assert_done!(output_section_command("LINKER_VERSION;"));
+        assert_done!(
+            output_section_command("CONSTRUCTORS_END"),
+            OutputSectionCommand::InputSection {
+                flags: None,
+                file: SectionPattern::Simple("CONSTRUCTORS_END".into()),
+                sections: vec![],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            output_section_command("ASCIZ_table.o(.data)"),
+            OutputSectionCommand::InputSection {
+                flags: None,
+                file: SectionPattern::Simple("ASCIZ_table.o".into()),
+                sections: vec![SectionPattern::Simple(".data".into())],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(output_section_command("SQUAD(-1)"));
+        assert_done!(
+            output_section_command("ASCIZ \"hello world\""),
+            OutputSectionCommand::Asciz {
+                string: "hello world".into(),
+                span: SourceSpan::default(),
+            }
+        );
+        assert_fail!(output_section_command("ASCIZ hello"));
+        assert_done!(
+            output_section_command("FILL(0x123456789abcdef0123)"),
+            OutputSectionCommand::FillPattern {
+                pattern: vec![0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x01, 0x23],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            output_section_command("FILL(0x0090)"),
+            OutputSectionCommand::FillPattern {
+                pattern: vec![0x00, 0x90],
+                span: SourceSpan::default(),
+            }
+        );
+        assert!(matches!(
+            output_section_command("FILL(0x90 + 1)"),
+            Ok(("", OutputSectionCommand::Fill { .. }))
+        ));
+        assert!(matches!(
+            output_section_command("FILL(144)"),
+            Ok(("", OutputSectionCommand::Fill { .. }))
+        ));
+        assert_fail!(output_section_command("FILL(0x90zz)"));
         assert_done!(output_section_command("*"));
 
         assert_done!(statement_osc("ASSERT(SIZEOF(.upper)==0,\"Test\");"));
@@ -325,11 +834,153 @@ mod tests {
             "ASSERT(SIZEOF(.upper)==0,\"Test\");",
         ));
         assert_done!(output_section_command("FILL(0xff);"));
+        assert_done!(
+            output_section_command("INCLUDE text.ld"),
+            OutputSectionCommand::Include {
+                file: "text.ld".into(),
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(output_sc(".text : { INCLUDE text.ld; *(.text) }"));
+
+        assert_done!(
+            output_section_command("INPUT_SECTION_FLAGS (SHF_WRITE & !SHF_EXECINSTR) *(.data*)"),
+            OutputSectionCommand::InputSection {
+                flags: Some(InputSectionFlags {
+                    required: vec!["SHF_WRITE".into()],
+                    excluded: vec!["SHF_EXECINSTR".into()],
+                }),
+                file: SectionPattern::Simple("*".into()),
+                sections: vec![SectionPattern::Simple(".data*".into())],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(output_section_command(
+            "KEEP(INPUT_SECTION_FLAGS(!SHF_TLS) *(SORT(.init_array.*)))",
+        ));
+        assert_fail!(output_section_command(
+            "INPUT_SECTION_FLAGS(SHF_WRITE &) *(.data)"
+        ));
+        assert_fail!(output_section_command("INPUT_SECTION_FLAGS() *(.data)"));
+        assert_eq!(
+            " SHF_WRITE & !SHF_EXECINSTR ".parse::<InputSectionFlags>(),
+            Ok(InputSectionFlags {
+                required: vec!["SHF_WRITE".into()],
+                excluded: vec!["SHF_EXECINSTR".into()],
+            })
+        );
+        assert!("".parse::<InputSectionFlags>().is_err());
+        assert!("SHF_WRITE &".parse::<InputSectionFlags>().is_err());
+        assert!("A B".parse::<InputSectionFlags>().is_err());
 
         assert_done!(output_sc("/DISCARD/ : { *(.note.GNU-stack) }"));
         assert_done!(output_sc(".DATA : { [A-Z]*(.data) }"));
         assert_done!(output_sc(".infoD     : {} > INFOD"));
 
         assert_done!(output_sc(".a:{*(.b .c)*(.d .e)}"));
+        assert_done!(output_sc(".text : { *(.text) } :text"));
+        assert_done!(output_sc(
+            ".data : { *(.data) } >RAM AT>ROM :data :dyn =0xff"
+        ));
+        assert_done!(output_sc(
+            ".data : { *(.data) } > RAM AT > ROM : data : dyn = 0xff,"
+        ));
+    }
+
+    #[test]
+    fn test_overlay() {
+        assert_done!(
+            overlay_section(".ov1 { *(.ov1) } :ov =0xff"),
+            OverlaySection {
+                name: ".ov1".into(),
+                content: vec![OutputSectionCommand::InputSection {
+                    flags: None,
+                    file: SectionPattern::Simple("*".into()),
+                    sections: vec![SectionPattern::Simple(".ov1".into())],
+                    span: SourceSpan::default(),
+                }],
+                phdrs: vec!["ov".into()],
+                fillexp: None,
+                fill_pattern: Some(vec![0xff]),
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(overlay_section(".ov2 { *(.ov2) } : a : b = 0,"));
+        assert_done!(overlay_section(".empty { }"));
+        assert_fail!(overlay_section(".ov1 { *(.ov1) "));
+
+        let overlay = |input| match overlay_sc(input) {
+            Ok(("", SectionCommand::Overlay(overlay))) => overlay,
+            result => panic!("{:?}", result),
+        };
+        let full = overlay(
+            "OVERLAY 0x1000 : NOCROSSREFS AT (0x4000) { .a { *(.a) } .b { *(.b) } :ov } \
+             >RAM AT>ROM :text =0x90,",
+        );
+        assert_eq!(
+            full.start.and_then(|start| start.constant_value()),
+            Some(0x1000)
+        );
+        assert!(full.nocrossrefs);
+        assert_eq!(
+            full.lma_address.and_then(|lma| lma.constant_value()),
+            Some(0x4000)
+        );
+        assert_eq!(full.sections.len(), 2);
+        assert_eq!(full.sections[1].phdrs, vec!["ov".to_string()]);
+        assert_eq!(full.region.as_deref(), Some("RAM"));
+        assert_eq!(full.lma_region.as_deref(), Some("ROM"));
+        assert_eq!(full.phdrs, vec!["text".to_string()]);
+        assert!(full.fillexp.is_none());
+        assert_eq!(full.fill_pattern, Some(vec![0x90]));
+
+        let bare = overlay("OVERLAY : { .a { *(.a) } }");
+        assert!(bare.start.is_none());
+        assert!(!bare.nocrossrefs);
+        assert!(bare.lma_address.is_none());
+        assert!(bare.region.is_none());
+
+        assert_done!(overlay_sc(
+            "OVERLAY: AT(ADDR(.text)) { .a { } } > RAM AT > ROM : x = 1"
+        ));
+        assert_fail!(overlay_sc("OVERLAY 0x1000 : { .a { *(.a) }"));
+        assert_fail!(overlay_sc("OVERLAYS : { }"));
+    }
+
+    #[test]
+    fn test_section_types() {
+        let section = |input| match output_sc(input) {
+            Ok(("", SectionCommand::OutputSection(section))) => section,
+            result => panic!("{:?}", result),
+        };
+        let text = section(".text (READONLY) : { *(.text) }");
+        assert_eq!(text.s_type, Some(OutputSectionType::ReadOnly));
+
+        let bss = section(".bss 0x2000 (NOLOAD) : { *(.bss) }");
+        assert_eq!(bss.s_type, Some(OutputSectionType::NoLoad));
+        assert!(bss.vma_address.is_some());
+
+        let note = section(".note (TYPE = SHT_NOTE) : { *(.note*) }");
+        assert!(matches!(
+            note.s_type,
+            Some(OutputSectionType::Type(ref expr)) if **expr == Expression::Ident("SHT_NOTE".into())
+        ));
+
+        let custom = section(".custom (READONLY (TYPE = 0x70000001)) : { *(.custom) }");
+        assert!(matches!(
+            custom.s_type,
+            Some(OutputSectionType::ReadOnlyType(ref expr)) if expr.constant_value() == Some(0x70000001)
+        ));
+
+        let info = section(".comment 0 ( INFO ) : { *(.comment) }");
+        assert_eq!(info.s_type, Some(OutputSectionType::Info));
+
+        let addressed = section(".data (ADDR(.text) + 0x100) : { *(.data) }");
+        assert!(addressed.vma_address.is_some());
+        assert_eq!(addressed.s_type, None);
+
+        assert_fail!(output_sc(".bad (TYPE = ) : { *(.bad) }"));
+        assert_fail!(output_sc(".bss (NOLOAD) (READONLY) : { *(.bss) }"));
+        assert_fail!(output_sc(".bss (NOLOAD) 0x2000 (READONLY) : { *(.bss) }"));
     }
 }
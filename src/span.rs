@@ -0,0 +1,711 @@
+use super::commands::Command;
+use super::error::IResult;
+use super::expressions::Expression;
+use super::memory::{MemoryCommand, Region};
+use super::phdrs::{ProgramHeader, ProgramHeaderType};
+use super::script::RootItem;
+use super::sections::{
+    OutputSection, OutputSectionCommand, OutputSectionType, Overlay, OverlaySection, SectionCommand,
+};
+use super::statements::Statement;
+use super::version::VersionNode;
+use std::fmt;
+
+/// Location of a node in the source it was parsed from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    /// Byte offset of the start of the node
+    pub start: usize,
+    /// Byte offset just past the end of the node
+    pub end: usize,
+    /// Line of the start of the node, starting at 1
+    pub line: usize,
+    /// Column of the start of the node in characters, starting at 1
+    pub column: usize,
+}
+
+/// Span attached to a node. It is empty for nodes that were not parsed from
+/// a whole script, e.g. built with the builder, and it never takes part in
+/// comparisons so that parsed and built nodes compare equal.
+#[derive(Default, Clone, Copy)]
+pub struct SourceSpan {
+    location: Location,
+    /// Index of the file the node was loaded from, see `Loader`
+    file: Option<usize>,
+}
+
+#[derive(Default, Clone, Copy)]
+enum Location {
+    #[default]
+    None,
+    /// Length of the remaining input at the start and at the end of the
+    /// node, recorded while parsing before the whole source is known
+    Pending {
+        start: usize,
+        end: usize,
+    },
+    Resolved(Span),
+}
+
+impl SourceSpan {
+    pub fn get(&self) -> Option<Span> {
+        match self.location {
+            Location::Resolved(span) => Some(span),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn file(&self) -> Option<usize> {
+        self.file
+    }
+
+    pub(crate) fn set_file(&mut self, file: usize) {
+        self.file = Some(file);
+    }
+
+    /// Span of the input consumed between `input` and `rest`, minus trailing
+    /// whitespace and comments
+    pub(crate) fn consumed(input: &str, rest: &str) -> SourceSpan {
+        let mut consumed = &input[..input.len() - rest.len()];
+        loop {
+            consumed = consumed.trim_end();
+            match consumed.strip_suffix("*/").and_then(|c| c.rfind("/*")) {
+                Some(comment) => consumed = &consumed[..comment],
+                None => break,
+            }
+        }
+        SourceSpan {
+            location: Location::Pending {
+                start: input.len(),
+                end: input.len() - consumed.len(),
+            },
+            file: None,
+        }
+    }
+}
+
+impl From<Span> for SourceSpan {
+    fn from(span: Span) -> Self {
+        SourceSpan {
+            location: Location::Resolved(span),
+            file: None,
+        }
+    }
+}
+
+impl PartialEq for SourceSpan {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+/// Nodes that record where they were parsed from.
+///
+/// Every node has a span except the leaves `Expression::Ident` and
+/// `Expression::Number`, which stay plain tuple variants so that code
+/// building and matching them keeps compiling. Their `span()` is `None`, and
+/// diagnostics about them point at the enclosing expression or statement.
+pub trait Spanned {
+    fn source_span(&self) -> &SourceSpan;
+
+    /// Location of the node in the parsed script, if it was parsed
+    fn span(&self) -> Option<Span> {
+        self.source_span().get()
+    }
+}
+
+pub(crate) trait Node: Spanned + VisitSpans {
+    fn span_mut(&mut self) -> &mut SourceSpan;
+}
+
+pub(crate) trait VisitSpans {
+    /// Calls `f` on the span of the node and of every node it contains
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan));
+}
+
+/// Runs `parser` and records the input it consumed, minus trailing whitespace
+/// and comments, as the span of the returned node.
+pub(crate) fn spanned<'a, O: Node>(
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    move |input: &'a str| {
+        let (rest, mut node) = parser(input)?;
+        *node.span_mut() = SourceSpan::consumed(input, rest);
+        Ok((rest, node))
+    }
+}
+
+/// Turns the spans recorded while parsing `source` into offsets and lines.
+pub(crate) fn resolve<N: VisitSpans>(nodes: &mut [N], source: &str) {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let mut resolve_span = |span: &mut SourceSpan| {
+        if let Location::Pending { start, end } = span.location {
+            let start = source.len() - start;
+            let line = line_starts.partition_point(|&line_start| line_start <= start);
+            let column = source[line_starts[line - 1]..start].chars().count() + 1;
+            span.location = Location::Resolved(Span {
+                start,
+                end: source.len() - end,
+                line,
+                column,
+            });
+        }
+    };
+    for node in nodes {
+        node.visit_spans(&mut resolve_span);
+    }
+}
+
+impl<N: Spanned> Spanned for Box<N> {
+    fn source_span(&self) -> &SourceSpan {
+        (**self).source_span()
+    }
+}
+
+impl<N: VisitSpans> VisitSpans for Box<N> {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        (**self).visit_spans(f)
+    }
+}
+
+impl<N: VisitSpans> VisitSpans for Option<N> {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        if let Some(node) = self {
+            node.visit_spans(f);
+        }
+    }
+}
+
+impl<N: VisitSpans> VisitSpans for Vec<N> {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        for node in self {
+            node.visit_spans(f);
+        }
+    }
+}
+
+impl Spanned for RootItem {
+    fn source_span(&self) -> &SourceSpan {
+        match self {
+            RootItem::Statement(statement) => statement.source_span(),
+            RootItem::Command(command) => command.source_span(),
+            RootItem::Memory { span, .. }
+            | RootItem::Sections { span, .. }
+            | RootItem::Phdrs { span, .. }
+            | RootItem::Version { span, .. } => span,
+        }
+    }
+}
+
+impl Node for RootItem {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        match self {
+            RootItem::Statement(statement) => statement.span_mut(),
+            RootItem::Command(command) => command.span_mut(),
+            RootItem::Memory { span, .. }
+            | RootItem::Sections { span, .. }
+            | RootItem::Phdrs { span, .. }
+            | RootItem::Version { span, .. } => span,
+        }
+    }
+}
+
+impl VisitSpans for RootItem {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        match self {
+            RootItem::Statement(statement) => statement.visit_spans(f),
+            RootItem::Command(command) => command.visit_spans(f),
+            RootItem::Memory { commands, span } => {
+                f(span);
+                commands.visit_spans(f);
+            }
+            RootItem::Sections { list, span } => {
+                f(span);
+                list.visit_spans(f);
+            }
+            RootItem::Phdrs { headers, span } => {
+                f(span);
+                headers.visit_spans(f);
+            }
+            RootItem::Version { nodes, span } => {
+                f(span);
+                nodes.visit_spans(f);
+            }
+        }
+    }
+}
+
+impl Spanned for Statement {
+    fn source_span(&self) -> &SourceSpan {
+        match self {
+            Statement::Assign { span, .. }
+            | Statement::Hidden { span, .. }
+            | Statement::Provide { span, .. }
+            | Statement::ProvideHidden { span, .. }
+            | Statement::Assert { span, .. } => span,
+        }
+    }
+}
+
+impl Node for Statement {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        match self {
+            Statement::Assign { span, .. }
+            | Statement::Hidden { span, .. }
+            | Statement::Provide { span, .. }
+            | Statement::ProvideHidden { span, .. }
+            | Statement::Assert { span, .. } => span,
+        }
+    }
+}
+
+impl VisitSpans for Statement {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        match self {
+            Statement::Assign {
+                expression, span, ..
+            }
+            | Statement::Hidden {
+                expression, span, ..
+            }
+            | Statement::Provide {
+                expression, span, ..
+            }
+            | Statement::ProvideHidden {
+                expression, span, ..
+            }
+            | Statement::Assert {
+                expr: expression,
+                span,
+                ..
+            } => {
+                f(span);
+                expression.visit_spans(f);
+            }
+        }
+    }
+}
+
+impl Spanned for Command {
+    fn source_span(&self) -> &SourceSpan {
+        match self {
+            Command::Nullary { span, .. }
+            | Command::Call { span, .. }
+            | Command::Include { span, .. }
+            | Command::Insert { span, .. }
+            | Command::Input { span, .. }
+            | Command::Group { span, .. }
+            | Command::Extern { span, .. }
+            | Command::Startup { span, .. }
+            | Command::SearchDir { span, .. } => span,
+        }
+    }
+}
+
+impl Node for Command {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        match self {
+            Command::Nullary { span, .. }
+            | Command::Call { span, .. }
+            | Command::Include { span, .. }
+            | Command::Insert { span, .. }
+            | Command::Input { span, .. }
+            | Command::Group { span, .. }
+            | Command::Extern { span, .. }
+            | Command::Startup { span, .. }
+            | Command::SearchDir { span, .. } => span,
+        }
+    }
+}
+
+impl VisitSpans for Command {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        f(self.span_mut());
+        if let Command::Call { arguments, .. } = self {
+            arguments.visit_spans(f);
+        }
+    }
+}
+
+/// Span of the leaves of an expression, which don't record one
+static NO_SPAN: SourceSpan = SourceSpan {
+    location: Location::None,
+    file: None,
+};
+
+impl Spanned for Expression {
+    fn source_span(&self) -> &SourceSpan {
+        match self {
+            Expression::Ident(_) | Expression::Number(_) => &NO_SPAN,
+            Expression::Call { span, .. }
+            | Expression::UnaryOp { span, .. }
+            | Expression::BinaryOp { span, .. }
+            | Expression::TernaryOp { span, .. } => span,
+        }
+    }
+}
+
+impl Expression {
+    /// Span of the expression, or `None` for identifiers and numbers
+    pub(crate) fn span_mut(&mut self) -> Option<&mut SourceSpan> {
+        match self {
+            Expression::Ident(_) | Expression::Number(_) => None,
+            Expression::Call { span, .. }
+            | Expression::UnaryOp { span, .. }
+            | Expression::BinaryOp { span, .. }
+            | Expression::TernaryOp { span, .. } => Some(span),
+        }
+    }
+}
+
+impl VisitSpans for Expression {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        if let Some(span) = self.span_mut() {
+            f(span);
+        }
+        match self {
+            Expression::Ident(_) | Expression::Number(_) => {}
+            Expression::Call { arguments, .. } => arguments.visit_spans(f),
+            Expression::UnaryOp { right, .. } => right.visit_spans(f),
+            Expression::BinaryOp { left, right, .. } => {
+                left.visit_spans(f);
+                right.visit_spans(f);
+            }
+            Expression::TernaryOp {
+                condition,
+                left,
+                right,
+                ..
+            } => {
+                condition.visit_spans(f);
+                left.visit_spans(f);
+                right.visit_spans(f);
+            }
+        }
+    }
+}
+
+impl Spanned for MemoryCommand {
+    fn source_span(&self) -> &SourceSpan {
+        match self {
+            MemoryCommand::Region(region) => region.source_span(),
+            MemoryCommand::Include { span, .. } => span,
+        }
+    }
+}
+
+impl Node for MemoryCommand {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        match self {
+            MemoryCommand::Region(region) => region.span_mut(),
+            MemoryCommand::Include { span, .. } => span,
+        }
+    }
+}
+
+impl VisitSpans for MemoryCommand {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        match self {
+            MemoryCommand::Region(region) => region.visit_spans(f),
+            MemoryCommand::Include { span, .. } => f(span),
+        }
+    }
+}
+
+impl Spanned for Region {
+    fn source_span(&self) -> &SourceSpan {
+        &self.span
+    }
+}
+
+impl Node for Region {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        &mut self.span
+    }
+}
+
+impl VisitSpans for Region {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        f(&mut self.span);
+        self.origin.visit_spans(f);
+        self.length.visit_spans(f);
+    }
+}
+
+impl Spanned for ProgramHeader {
+    fn source_span(&self) -> &SourceSpan {
+        &self.span
+    }
+}
+
+impl Node for ProgramHeader {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        &mut self.span
+    }
+}
+
+impl VisitSpans for ProgramHeader {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        f(&mut self.span);
+        if let ProgramHeaderType::Value(value) = &mut self.p_type {
+            value.visit_spans(f);
+        }
+        self.at.visit_spans(f);
+        self.flags.visit_spans(f);
+    }
+}
+
+impl Spanned for VersionNode {
+    fn source_span(&self) -> &SourceSpan {
+        &self.span
+    }
+}
+
+impl Node for VersionNode {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        &mut self.span
+    }
+}
+
+impl VisitSpans for VersionNode {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        f(&mut self.span);
+    }
+}
+
+impl Spanned for SectionCommand {
+    fn source_span(&self) -> &SourceSpan {
+        match self {
+            SectionCommand::Statement(statement) => statement.source_span(),
+            SectionCommand::Command(command) => command.source_span(),
+            SectionCommand::OutputSection(section) => section.source_span(),
+            SectionCommand::Overlay(overlay) => overlay.source_span(),
+        }
+    }
+}
+
+impl Node for SectionCommand {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        match self {
+            SectionCommand::Statement(statement) => statement.span_mut(),
+            SectionCommand::Command(command) => command.span_mut(),
+            SectionCommand::OutputSection(section) => section.span_mut(),
+            SectionCommand::Overlay(overlay) => overlay.span_mut(),
+        }
+    }
+}
+
+impl VisitSpans for SectionCommand {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        match self {
+            SectionCommand::Statement(statement) => statement.visit_spans(f),
+            SectionCommand::Command(command) => command.visit_spans(f),
+            SectionCommand::OutputSection(section) => section.visit_spans(f),
+            SectionCommand::Overlay(overlay) => overlay.visit_spans(f),
+        }
+    }
+}
+
+impl Spanned for OutputSection {
+    fn source_span(&self) -> &SourceSpan {
+        &self.span
+    }
+}
+
+impl Node for OutputSection {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        &mut self.span
+    }
+}
+
+impl VisitSpans for OutputSection {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        f(&mut self.span);
+        self.vma_address.visit_spans(f);
+        if let Some(OutputSectionType::Type(expr) | OutputSectionType::ReadOnlyType(expr)) =
+            &mut self.s_type
+        {
+            expr.visit_spans(f);
+        }
+        self.lma_address.visit_spans(f);
+        self.section_align.visit_spans(f);
+        self.subsection_align.visit_spans(f);
+        self.content.visit_spans(f);
+        self.fillexp.visit_spans(f);
+    }
+}
+
+impl Spanned for Overlay {
+    fn source_span(&self) -> &SourceSpan {
+        &self.span
+    }
+}
+
+impl Node for Overlay {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        &mut self.span
+    }
+}
+
+impl VisitSpans for Overlay {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        f(&mut self.span);
+        self.start.visit_spans(f);
+        self.lma_address.visit_spans(f);
+        self.sections.visit_spans(f);
+        self.fillexp.visit_spans(f);
+    }
+}
+
+impl Spanned for OverlaySection {
+    fn source_span(&self) -> &SourceSpan {
+        &self.span
+    }
+}
+
+impl Node for OverlaySection {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        &mut self.span
+    }
+}
+
+impl VisitSpans for OverlaySection {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        f(&mut self.span);
+        self.content.visit_spans(f);
+        self.fillexp.visit_spans(f);
+    }
+}
+
+impl Spanned for OutputSectionCommand {
+    fn source_span(&self) -> &SourceSpan {
+        match self {
+            OutputSectionCommand::Statement(statement) => statement.source_span(),
+            OutputSectionCommand::Fill { span, .. }
+            | OutputSectionCommand::Data { span, .. }
+            | OutputSectionCommand::InputSection { span, .. }
+            | OutputSectionCommand::KeepInputSection { span, .. }
+            | OutputSectionCommand::Include { span, .. }
+            | OutputSectionCommand::FillPattern { span, .. }
+            | OutputSectionCommand::Asciz { span, .. }
+            | OutputSectionCommand::LinkerVersion { span }
+            | OutputSectionCommand::Constructors { span, .. }
+            | OutputSectionCommand::CreateObjectSymbols { span } => span,
+        }
+    }
+}
+
+impl Node for OutputSectionCommand {
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        match self {
+            OutputSectionCommand::Statement(statement) => statement.span_mut(),
+            OutputSectionCommand::Fill { span, .. }
+            | OutputSectionCommand::Data { span, .. }
+            | OutputSectionCommand::InputSection { span, .. }
+            | OutputSectionCommand::KeepInputSection { span, .. }
+            | OutputSectionCommand::Include { span, .. }
+            | OutputSectionCommand::FillPattern { span, .. }
+            | OutputSectionCommand::Asciz { span, .. }
+            | OutputSectionCommand::LinkerVersion { span }
+            | OutputSectionCommand::Constructors { span, .. }
+            | OutputSectionCommand::CreateObjectSymbols { span } => span,
+        }
+    }
+}
+
+impl VisitSpans for OutputSectionCommand {
+    fn visit_spans(&mut self, f: &mut dyn FnMut(&mut SourceSpan)) {
+        match self {
+            OutputSectionCommand::Statement(statement) => statement.visit_spans(f),
+            OutputSectionCommand::Fill { expr, span } => {
+                f(span);
+                expr.visit_spans(f);
+            }
+            OutputSectionCommand::Data { value, span, .. } => {
+                f(span);
+                value.visit_spans(f);
+            }
+            OutputSectionCommand::InputSection { span, .. }
+            | OutputSectionCommand::KeepInputSection { span, .. }
+            | OutputSectionCommand::Include { span, .. }
+            | OutputSectionCommand::FillPattern { span, .. }
+            | OutputSectionCommand::Asciz { span, .. }
+            | OutputSectionCommand::LinkerVersion { span }
+            | OutputSectionCommand::Constructors { span, .. }
+            | OutputSectionCommand::CreateObjectSymbols { span } => f(span),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::span::*;
+    use crate::{parse, OutputSectionCommand};
+
+    fn text<'a>(source: &'a str, node: &impl Spanned) -> &'a str {
+        let span = node.span().unwrap();
+        &source[span.start..span.end]
+    }
+
+    #[test]
+    fn test_spans() {
+        let source = "MEMORY {\n  ROM : ORIGIN = 0, LENGTH = 1K /* rom */\n}\n\
+                      SECTIONS {\n  .text : {\n    *(.text)\n    _etext = (. + 3) & ~3;\n  } > ROM\n}";
+        let items = parse(source).unwrap();
+        assert_eq!(
+            text(source, &items[0]),
+            &source[..source.find("}\n").unwrap() + 1]
+        );
+
+        let RootItem::Memory { commands, .. } = &items[0] else {
+            panic!("expected MEMORY");
+        };
+        let rom = commands[0].region().unwrap();
+        assert_eq!(text(source, rom), "ROM : ORIGIN = 0, LENGTH = 1K");
+        let span = rom.span().unwrap();
+        assert_eq!((span.line, span.column), (2, 3));
+        assert_eq!(rom.length.span(), None);
+
+        let RootItem::Sections { list, .. } = &items[1] else {
+            panic!("expected SECTIONS");
+        };
+        let SectionCommand::OutputSection(section) = &list[0] else {
+            panic!("expected an output section");
+        };
+        assert!(text(source, section).starts_with(".text : {"));
+        assert!(text(source, section).ends_with("} > ROM"));
+        assert_eq!(text(source, &section.content[0]), "*(.text)");
+
+        let OutputSectionCommand::Statement(Statement::Assign { expression, .. }) =
+            &section.content[1]
+        else {
+            panic!("expected an assignment");
+        };
+        assert_eq!(text(source, &section.content[1]), "_etext = (. + 3) & ~3;");
+        assert_eq!(text(source, expression.as_ref()), "(. + 3) & ~3");
+        let Expression::BinaryOp { left, right, .. } = expression.as_ref() else {
+            panic!("expected a binary operation");
+        };
+        assert_eq!(text(source, left.as_ref()), "(. + 3)");
+        assert_eq!(text(source, right.as_ref()), "~3");
+        let span = right.span().unwrap();
+        assert_eq!((span.line, span.column), (7, 24));
+    }
+
+    #[test]
+    fn test_spans_ignored_by_eq() {
+        let parsed = parse("ENTRY(_start);").unwrap();
+        assert!(parsed[0].span().is_some());
+        let built = RootItem::Command(Command::call("ENTRY", ["_start"]));
+        assert!(built.span().is_none());
+        assert_eq!(parsed[0], built);
+    }
+}
@@ -1,12 +1,12 @@
+use super::error::{tag, IResult};
 use super::expressions::expression;
 use super::expressions::Expression;
 use super::idents::{string, symbol};
+use super::span::{spanned, SourceSpan};
 use super::whitespace::opt_space;
 use nom::branch::alt;
-use nom::bytes::complete::tag;
 use nom::combinator::map;
 use nom::combinator::opt;
-use nom::IResult;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum AssignOperator {
@@ -27,22 +27,27 @@ pub enum Statement {
         name: String,
         operator: AssignOperator,
         expression: Box<Expression>,
+        span: SourceSpan,
     },
     Hidden {
         name: String,
         expression: Box<Expression>,
+        span: SourceSpan,
     },
     Provide {
         name: String,
         expression: Box<Expression>,
+        span: SourceSpan,
     },
     ProvideHidden {
         name: String,
         expression: Box<Expression>,
+        span: SourceSpan,
     },
     Assert {
         expr: Box<Expression>,
         text: String,
+        span: SourceSpan,
     },
 }
 
@@ -88,14 +93,17 @@ fn special_assign(input: &str) -> IResult<&str, Statement> {
             "HIDDEN" => Statement::Hidden {
                 name: name.into(),
                 expression: Box::new(expr),
+                span: SourceSpan::default(),
             },
             "PROVIDE" => Statement::Provide {
                 name: name.into(),
                 expression: Box::new(expr),
+                span: SourceSpan::default(),
             },
             "PROVIDE_HIDDEN" => Statement::ProvideHidden {
                 name: name.into(),
                 expression: Box::new(expr),
+                span: SourceSpan::default(),
             },
             _ => panic!("invalid assign keyword"),
         },
@@ -114,6 +122,7 @@ fn assign(input: &str) -> IResult<&str, Statement> {
             name: name.into(),
             operator: op,
             expression: Box::new(expr),
+            span: SourceSpan::default(),
         },
     ))
 }
@@ -131,12 +140,13 @@ fn assert_stmt(input: &str) -> IResult<&str, Statement> {
         Statement::Assert {
             expr: Box::new(expr),
             text: text.into(),
+            span: SourceSpan::default(),
         },
     ))
 }
 
 pub fn statement(input: &str) -> IResult<&str, Statement> {
-    alt((special_assign, assign, assert_stmt))(input)
+    spanned(alt((special_assign, assign, assert_stmt)))(input)
 }
 
 #[cfg(test)]
@@ -152,6 +162,7 @@ mod tests {
                 name: "A".into(),
                 operator: AssignOperator::Equals,
                 expression: Box::new(Expression::Number(11)),
+                span: SourceSpan::default(),
             }
         );
         assert_done!(
@@ -159,6 +170,7 @@ mod tests {
             Statement::Provide {
                 name: "x".into(),
                 expression: Box::new(Expression::Ident("x".into())),
+                span: SourceSpan::default(),
             }
         );
         assert_done!(statement("PROBLEM += HELLO ( WORLD , 0 ) + 1 ;"));
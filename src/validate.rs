@@ -0,0 +1,398 @@
+use crate::commands::Command;
+use crate::diagnostic::Diagnostic;
+use crate::expressions::Expression;
+use crate::memory::{MemoryCommand, Region};
+use crate::phdrs::{ProgramHeader, ProgramHeaderType};
+use crate::script::RootItem;
+use crate::sections::{OutputSectionCommand, SectionCommand};
+use crate::span::{Span, Spanned};
+use crate::statements::Statement;
+use std::collections::HashSet;
+
+/// Checks a parsed script for mistakes that the linker only reports at link
+/// time, such as references to undeclared memory regions or sections.
+///
+/// Diagnostics are ordered by their position in the script.
+pub fn validate(items: &[RootItem]) -> Vec<Diagnostic> {
+    let regions: Vec<&Region> = items
+        .iter()
+        .flat_map(|item| match item {
+            RootItem::Memory { commands, .. } => commands.as_slice(),
+            _ => &[],
+        })
+        .filter_map(MemoryCommand::region)
+        .collect();
+    let sections = output_sections(items);
+
+    let mut diagnostics = Vec::new();
+    check_regions(&regions, &mut diagnostics);
+    check_sections(&sections, &mut diagnostics);
+
+    let region_names: HashSet<&str> = regions.iter().map(|region| region.name.as_str()).collect();
+    let section_names: HashSet<&str> = sections.iter().map(|section| section.name).collect();
+    for section in &sections {
+        for region in [section.region, section.lma_region].into_iter().flatten() {
+            if !region_names.contains(region) {
+                diagnostics.push(Diagnostic::error(
+                    "unknown-region",
+                    format!(
+                        "section `{}` is placed in undeclared memory region `{}`",
+                        section.name, region
+                    ),
+                    section.span,
+                ));
+            }
+        }
+    }
+    items.visit_expressions(&mut |expression| {
+        let Expression::Call {
+            function,
+            arguments,
+            ..
+        } = expression
+        else {
+            return;
+        };
+        let [Expression::Ident(name)] = arguments.as_slice() else {
+            return;
+        };
+        let (code, kind, known) = match function.as_str() {
+            "ORIGIN" | "LENGTH" => ("unknown-region", "memory region", &region_names),
+            "ADDR" | "LOADADDR" | "SIZEOF" | "ALIGNOF" => {
+                ("unknown-section", "section", &section_names)
+            }
+            _ => return,
+        };
+        if !known.contains(name.as_str()) {
+            diagnostics.push(Diagnostic::error(
+                code,
+                format!("`{}` refers to undeclared {} `{}`", function, kind, name),
+                expression.span(),
+            ));
+        }
+    });
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map_or(usize::MAX, |span| span.start));
+    diagnostics
+}
+
+fn check_regions(regions: &[&Region], diagnostics: &mut Vec<Diagnostic>) {
+    for (i, region) in regions.iter().enumerate() {
+        let earlier = &regions[..i];
+        if earlier.iter().any(|other| other.name == region.name) {
+            diagnostics.push(Diagnostic::error(
+                "duplicate-region",
+                format!("memory region `{}` is declared more than once", region.name),
+                region.span(),
+            ));
+            continue;
+        }
+        let Some(range) = address_range(region) else {
+            continue;
+        };
+        let overlapping = earlier.iter().find(|other| {
+            other.name != region.name
+                && address_range(other).is_some_and(|other| other.0 < range.1 && range.0 < other.1)
+        });
+        if let Some(other) = overlapping {
+            diagnostics.push(Diagnostic::warning(
+                "overlapping-regions",
+                format!(
+                    "memory region `{}` overlaps memory region `{}`",
+                    region.name, other.name
+                ),
+                region.span(),
+            ));
+        }
+    }
+}
+
+/// Start and end of a region with constant, non-zero bounds
+fn address_range(region: &Region) -> Option<(u128, u128)> {
+    let origin = u128::from(region.origin_value()?);
+    let length = u128::from(region.length_value()?);
+    (length > 0).then_some((origin, origin + length))
+}
+
+fn check_sections(sections: &[OutputSectionInfo], diagnostics: &mut Vec<Diagnostic>) {
+    for (i, section) in sections.iter().enumerate() {
+        if section.name == "/DISCARD/" {
+            continue;
+        }
+        if sections[..i].iter().any(|other| other.name == section.name) {
+            diagnostics.push(Diagnostic::warning(
+                "duplicate-section",
+                format!(
+                    "output section `{}` is defined more than once",
+                    section.name
+                ),
+                section.span,
+            ));
+        }
+    }
+}
+
+struct OutputSectionInfo<'a> {
+    name: &'a str,
+    region: Option<&'a str>,
+    lma_region: Option<&'a str>,
+    span: Option<Span>,
+}
+
+/// Output sections of the script, including the sections of overlays
+fn output_sections(items: &[RootItem]) -> Vec<OutputSectionInfo<'_>> {
+    let mut sections = Vec::new();
+    for item in items {
+        let RootItem::Sections { list, .. } = item else {
+            continue;
+        };
+        for command in list {
+            match command {
+                SectionCommand::OutputSection(section) => sections.push(OutputSectionInfo {
+                    name: &section.name,
+                    region: section.region.as_deref(),
+                    lma_region: section.lma_region.as_deref(),
+                    span: section.span(),
+                }),
+                SectionCommand::Overlay(overlay) => {
+                    for section in &overlay.sections {
+                        sections.push(OutputSectionInfo {
+                            name: &section.name,
+                            region: overlay.region.as_deref(),
+                            lma_region: overlay.lma_region.as_deref(),
+                            span: section.span(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    sections
+}
+
+pub(crate) trait VisitExpressions {
+    /// Calls `f` on every expression in the node, outer expressions before
+    /// their operands
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression));
+}
+
+impl<N: VisitExpressions> VisitExpressions for Box<N> {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        (**self).visit_expressions(f)
+    }
+}
+
+impl<N: VisitExpressions> VisitExpressions for Option<N> {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        if let Some(node) = self {
+            node.visit_expressions(f);
+        }
+    }
+}
+
+impl<N: VisitExpressions> VisitExpressions for [N] {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        for node in self {
+            node.visit_expressions(f);
+        }
+    }
+}
+
+impl<N: VisitExpressions> VisitExpressions for Vec<N> {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        self.as_slice().visit_expressions(f)
+    }
+}
+
+impl VisitExpressions for Expression {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        f(self);
+        match self {
+            Expression::Ident(..) | Expression::Number(..) => {}
+            Expression::Call { arguments, .. } => arguments.visit_expressions(f),
+            Expression::UnaryOp { right, .. } => right.visit_expressions(f),
+            Expression::BinaryOp { left, right, .. } => {
+                left.visit_expressions(f);
+                right.visit_expressions(f);
+            }
+            Expression::TernaryOp {
+                condition,
+                left,
+                right,
+                ..
+            } => {
+                condition.visit_expressions(f);
+                left.visit_expressions(f);
+                right.visit_expressions(f);
+            }
+        }
+    }
+}
+
+impl VisitExpressions for RootItem {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        match self {
+            RootItem::Statement(statement) => statement.visit_expressions(f),
+            RootItem::Command(command) => command.visit_expressions(f),
+            RootItem::Memory { commands, .. } => commands.visit_expressions(f),
+            RootItem::Sections { list, .. } => list.visit_expressions(f),
+            RootItem::Phdrs { headers, .. } => headers.visit_expressions(f),
+            RootItem::Version { .. } => {}
+        }
+    }
+}
+
+impl VisitExpressions for Statement {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        match self {
+            Statement::Assign { expression, .. }
+            | Statement::Hidden { expression, .. }
+            | Statement::Provide { expression, .. }
+            | Statement::ProvideHidden { expression, .. }
+            | Statement::Assert {
+                expr: expression, ..
+            } => expression.visit_expressions(f),
+        }
+    }
+}
+
+impl VisitExpressions for Command {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        if let Command::Call { arguments, .. } = self {
+            arguments.visit_expressions(f);
+        }
+    }
+}
+
+impl VisitExpressions for MemoryCommand {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        if let MemoryCommand::Region(region) = self {
+            region.visit_expressions(f);
+        }
+    }
+}
+
+impl VisitExpressions for Region {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        self.origin.visit_expressions(f);
+        self.length.visit_expressions(f);
+    }
+}
+
+impl VisitExpressions for ProgramHeader {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        if let ProgramHeaderType::Value(value) = &self.p_type {
+            value.visit_expressions(f);
+        }
+        self.at.visit_expressions(f);
+        self.flags.visit_expressions(f);
+    }
+}
+
+impl VisitExpressions for SectionCommand {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        match self {
+            SectionCommand::Statement(statement) => statement.visit_expressions(f),
+            SectionCommand::Command(command) => command.visit_expressions(f),
+            SectionCommand::OutputSection(section) => {
+                section.vma_address.visit_expressions(f);
+                section.lma_address.visit_expressions(f);
+                section.section_align.visit_expressions(f);
+                section.subsection_align.visit_expressions(f);
+                section.content.visit_expressions(f);
+                section.fillexp.visit_expressions(f);
+            }
+            SectionCommand::Overlay(overlay) => {
+                overlay.start.visit_expressions(f);
+                overlay.lma_address.visit_expressions(f);
+                for section in &overlay.sections {
+                    section.content.visit_expressions(f);
+                    section.fillexp.visit_expressions(f);
+                }
+                overlay.fillexp.visit_expressions(f);
+            }
+        }
+    }
+}
+
+impl VisitExpressions for OutputSectionCommand {
+    fn visit_expressions<'a>(&'a self, f: &mut dyn FnMut(&'a Expression)) {
+        match self {
+            OutputSectionCommand::Statement(statement) => statement.visit_expressions(f),
+            OutputSectionCommand::Fill { expr, .. } => expr.visit_expressions(f),
+            OutputSectionCommand::Data { value, .. } => value.visit_expressions(f),
+            OutputSectionCommand::InputSection { .. }
+            | OutputSectionCommand::KeepInputSection { .. }
+            | OutputSectionCommand::Include { .. }
+            | OutputSectionCommand::FillPattern { .. }
+            | OutputSectionCommand::Asciz { .. }
+            | OutputSectionCommand::LinkerVersion { .. }
+            | OutputSectionCommand::Constructors { .. }
+            | OutputSectionCommand::CreateObjectSymbols { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Severity;
+    use crate::validate::*;
+
+    #[test]
+    fn test_valid() {
+        let script = crate::parse(
+            "MEMORY {
+                FLASH : ORIGIN = 0, LENGTH = 64K
+                RAM : ORIGIN = 0x20000000, LENGTH = 8K
+            }
+            SECTIONS {
+                .text : { *(.text) } > FLASH
+                .data : { *(.data) } > RAM AT> FLASH
+                _sidata = LOADADDR(.data);
+                _estack = ORIGIN(RAM) + LENGTH(RAM);
+                /DISCARD/ : { *(.comment) }
+                /DISCARD/ : { *(.note) }
+            }",
+        )
+        .unwrap();
+        assert_eq!(validate(&script), Vec::new());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let script = crate::parse(
+            "MEMORY {
+                FLASH : ORIGIN = 0, LENGTH = 64K
+                RAM : ORIGIN = 0x8000, LENGTH = 64K
+                FLASH : ORIGIN = 0x10000, LENGTH = 1K
+            }
+            SECTIONS {
+                .text : { *(.text) } > ROM
+                .data : { *(.data) } > RAM AT> FLASH
+                .data : { *(.data.*) } > RAM
+                _end = ADDR(.bss) + SIZEOF(.data) + LENGTH(SRAM);
+            }",
+        )
+        .unwrap();
+        let diagnostics: Vec<_> = validate(&script)
+            .into_iter()
+            .map(|d| (d.severity, d.code, d.span.unwrap().line))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (Severity::Warning, "overlapping-regions", 3),
+                (Severity::Error, "duplicate-region", 4),
+                (Severity::Error, "unknown-region", 7),
+                (Severity::Warning, "duplicate-section", 9),
+                (Severity::Error, "unknown-section", 10),
+                (Severity::Error, "unknown-region", 10),
+            ]
+        );
+        assert_eq!(
+            validate(&script)[2].message,
+            "section `.text` is placed in undeclared memory region `ROM`"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use super::error::{context, tag, IResult};
+use super::idents::{pattern, string, symbol};
+use super::span::{spanned, SourceSpan};
+use super::whitespace::opt_space;
+use nom::branch::alt;
+use nom::combinator::cut;
+use nom::combinator::map;
+use nom::combinator::opt;
+use nom::multi::{fold_many0, many0, separated_list0};
+use nom::sequence::{terminated, tuple};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VersionNode {
+    /// Version tag, `None` for the anonymous version node
+    pub name: Option<String>,
+    pub global: Vec<VersionEntry>,
+    pub local: Vec<VersionEntry>,
+    pub dependencies: Vec<String>,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum VersionEntry {
+    /// Symbol name, which may contain glob wildcards
+    Pattern(String),
+    /// Quoted symbol name, matched literally
+    Literal(String),
+    Extern {
+        language: String,
+        entries: Vec<VersionEntry>,
+    },
+}
+
+impl VersionEntry {
+    pub fn has_wildcard(&self) -> bool {
+        match self {
+            VersionEntry::Pattern(name) => name.contains(['*', '?', '[']),
+            _ => false,
+        }
+    }
+}
+
+enum Scope {
+    Global,
+    Local,
+}
+
+enum NodeItem {
+    Scope(Scope),
+    Entry(VersionEntry),
+}
+
+fn extern_entry(input: &str) -> IResult<&str, VersionEntry> {
+    let (input, _) = tuple((tag("extern"), opt_space))(input)?;
+    let (input, language) = string(input)?;
+    let (input, _) = wsc!(tag("{"))(input)?;
+    let (input, entries) = separated_list0(wsc!(tag(";")), version_entry)(input)?;
+    let (input, _) = tuple((wsc!(opt(tag(";"))), tag("}")))(input)?;
+    Ok((
+        input,
+        VersionEntry::Extern {
+            language: language.into(),
+            entries,
+        },
+    ))
+}
+
+fn version_entry(input: &str) -> IResult<&str, VersionEntry> {
+    alt((
+        extern_entry,
+        map(string, |name: &str| VersionEntry::Literal(name.into())),
+        map(pattern, |name: &str| VersionEntry::Pattern(name.into())),
+    ))(input)
+}
+
+fn scope(input: &str) -> IResult<&str, Scope> {
+    let (input, scope) = alt((
+        map(tag("global"), |_| Scope::Global),
+        map(tag("local"), |_| Scope::Local),
+    ))(input)?;
+    let (input, _) = tuple((opt_space, tag(":")))(input)?;
+    Ok((input, scope))
+}
+
+fn node_item(input: &str) -> IResult<&str, NodeItem> {
+    alt((
+        map(scope, NodeItem::Scope),
+        map(terminated(version_entry, wsc!(tag(";"))), NodeItem::Entry),
+    ))(input)
+}
+
+pub fn version_node(input: &str) -> IResult<&str, VersionNode> {
+    spanned(version_node_inner)(input)
+}
+
+fn version_node_inner(input: &str) -> IResult<&str, VersionNode> {
+    let (input, name) = opt(symbol)(input)?;
+    let (input, _) = wsc!(tag("{"))(input)?;
+    context(
+        move || match name {
+            Some(name) => format!("version node `{}`", name),
+            None => "anonymous version node".into(),
+        },
+        cut(move |input| version_node_body(input, name)),
+    )(input)
+}
+
+fn version_node_body<'a>(input: &'a str, name: Option<&str>) -> IResult<&'a str, VersionNode> {
+    let (input, (global, local, _)) = fold_many0(
+        wsc!(node_item),
+        || (vec![], vec![], Scope::Global),
+        |(mut global, mut local, scope), item| match item {
+            NodeItem::Scope(scope) => (global, local, scope),
+            NodeItem::Entry(entry) => {
+                match scope {
+                    Scope::Global => global.push(entry),
+                    Scope::Local => local.push(entry),
+                }
+                (global, local, scope)
+            }
+        },
+    )(input)?;
+    let (input, _) = wsc!(tag("}"))(input)?;
+    let (input, dependencies) = many0(wsc!(symbol))(input)?;
+    let (input, _) = tag(";")(input)?;
+    Ok((
+        input,
+        VersionNode {
+            name: name.map(String::from),
+            global,
+            local,
+            dependencies: dependencies.into_iter().map(String::from).collect(),
+            span: SourceSpan::default(),
+        },
+    ))
+}
+
+pub fn version_command(input: &str) -> IResult<&str, Vec<VersionNode>> {
+    let (input, _) = tuple((tag("VERSION"), wsc!(tag("{"))))(input)?;
+    context(
+        || "VERSION".into(),
+        cut(terminated(many0(wsc!(version_node)), tag("}"))),
+    )(input)
+}
+
+pub fn parse(input: &str) -> IResult<&str, Vec<VersionNode>> {
+    let (input, nodes) = many0(wsc!(version_node))(input)?;
+    let (input, _) = opt_space(input)?;
+    if !input.is_empty() {
+        // Parse the leftover again to report why it isn't a valid node
+        version_node(input)?;
+    }
+    Ok((input, nodes))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::version::*;
+
+    #[test]
+    fn test_version_node() {
+        assert_done!(
+            version_node("LIBFOO_1.0 { global: foo; bar*; local: *; };"),
+            VersionNode {
+                name: Some("LIBFOO_1.0".into()),
+                global: vec![
+                    VersionEntry::Pattern("foo".into()),
+                    VersionEntry::Pattern("bar*".into()),
+                ],
+                local: vec![VersionEntry::Pattern("*".into())],
+                dependencies: vec![],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            version_node("LIBFOO_2.0 { baz; } LIBFOO_1.0 ;"),
+            VersionNode {
+                name: Some("LIBFOO_2.0".into()),
+                global: vec![VersionEntry::Pattern("baz".into())],
+                local: vec![],
+                dependencies: vec!["LIBFOO_1.0".into()],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            version_node("{ global:\"sym\";local:*; };"),
+            VersionNode {
+                name: None,
+                global: vec![VersionEntry::Literal("sym".into())],
+                local: vec![VersionEntry::Pattern("*".into())],
+                dependencies: vec![],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(
+            version_node(
+                "V2 { global: extern \"C++\" { ns::*; \"std::string\" }; local: *; } V1 V0;"
+            ),
+            VersionNode {
+                name: Some("V2".into()),
+                global: vec![VersionEntry::Extern {
+                    language: "C++".into(),
+                    entries: vec![
+                        VersionEntry::Pattern("ns::*".into()),
+                        VersionEntry::Literal("std::string".into()),
+                    ],
+                }],
+                local: vec![VersionEntry::Pattern("*".into())],
+                dependencies: vec!["V1".into(), "V0".into()],
+                span: SourceSpan::default(),
+            }
+        );
+        assert_done!(version_node("EMPTY { };"));
+
+        assert_fail!(version_node("V1 { foo }"));
+        assert_fail!(version_node("V1 { foo; }"));
+    }
+
+    #[test]
+    fn test_version_command() {
+        assert_done_vec!(
+            version_command("VERSION { V1 { global: a; }; V2 { b; } V1; }"),
+            2
+        );
+        assert_done_vec!(version_command("VERSION{}"), 0);
+    }
+
+    #[test]
+    fn test_parse() {
+        let map = "
+            /* standalone version script */
+            LIBFOO_1.0 {
+                global:
+                    foo_*;
+                local:
+                    *;
+            };
+
+            LIBFOO_2.0 {
+                global:
+                    foo_new;
+            } LIBFOO_1.0;
+        ";
+        assert_done_vec!(parse(map), 2);
+        assert_done_vec!(parse(""), 0);
+        assert_fail!(parse("V1 { a; }; }"));
+    }
+
+    #[test]
+    fn test_has_wildcard() {
+        assert!(VersionEntry::Pattern("foo_*".into()).has_wildcard());
+        assert!(VersionEntry::Pattern("[ab]".into()).has_wildcard());
+        assert!(!VersionEntry::Pattern("foo".into()).has_wildcard());
+        assert!(!VersionEntry::Literal("foo*".into()).has_wildcard());
+    }
+}
@@ -1,3 +1,4 @@
+use super::error::IResult;
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_until},
@@ -5,7 +6,6 @@ use nom::{
     combinator::recognize,
     multi::{fold_many0, fold_many1},
     sequence::delimited,
-    IResult,
 };
 
 pub fn comment(input: &str) -> IResult<&str, &str> {